@@ -0,0 +1,76 @@
+//! Search speed at several depths, with throughput in nodes per second.
+//! Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use connect4::{find_next_move, find_next_move_with_stats, State, DEFAULT_PARALLEL_UNTIL_DEPTH};
+
+/// Positions from the `test_winning_moves` fixtures, with `Player::X` to move.
+/// In the first one, X can still win immediately.
+const FIXTURES: [&str; 2] = [
+    "x to move\n\
+     .....x.\n\
+     .....x.\n\
+     .....o.\n\
+     xo...x.\n\
+     xoo..x.\n\
+     xooo.x.\n",
+    "x to move\n\
+     .......\n\
+     ....o..\n\
+     ....oo.\n\
+     ..x.oxx\n\
+     ..o.xxo\n\
+     x.ooxox\n",
+];
+
+fn bench_search(c: &mut Criterion) {
+    let positions = [
+        ("empty", State::empty()),
+        ("fixture 1", State::from_text(FIXTURES[0]).unwrap()),
+        ("fixture 2", State::from_text(FIXTURES[1]).unwrap()),
+    ];
+    for &(group_name, parallel_until_depth) in &[
+        ("serial", u8::MAX),
+        ("parallel", DEFAULT_PARALLEL_UNTIL_DEPTH),
+    ] {
+        let mut group = c.benchmark_group(group_name);
+        group.sample_size(10);
+        for (name, state) in &positions {
+            for &depth in &[4, 6, 8] {
+                let (_, _, stats) = find_next_move_with_stats(state, depth, parallel_until_depth);
+                group.throughput(Throughput::Elements(stats.nodes));
+                group.bench_with_input(
+                    BenchmarkId::new(*name, depth),
+                    &(state, depth),
+                    |b, &(state, depth)| {
+                        b.iter(|| find_next_move(state, depth, parallel_until_depth))
+                    },
+                );
+            }
+        }
+        group.finish();
+    }
+}
+
+/// Parallelizing more levels of an 8-ply search keeps more threads busy,
+/// but prunes less. Throughput counts the nodes, which rise with the number
+/// of parallel levels, so compare the times to see what pays off.
+fn bench_parallel_threshold(c: &mut Criterion) {
+    let state = State::from_text(FIXTURES[1]).unwrap();
+    let mut group = c.benchmark_group("parallel until depth");
+    group.sample_size(10);
+    for &parallel_until_depth in &[8, 7, 6, 5, 4] {
+        let (_, _, stats) = find_next_move_with_stats(&state, 8, parallel_until_depth);
+        group.throughput(Throughput::Elements(stats.nodes));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(parallel_until_depth),
+            &parallel_until_depth,
+            |b, &parallel_until_depth| b.iter(|| find_next_move(&state, 8, parallel_until_depth)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_search, bench_parallel_threshold);
+criterion_main!(benches);
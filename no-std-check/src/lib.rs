@@ -0,0 +1,26 @@
+//! Checks that the engine builds without `std`:
+//!
+//! ```text
+//! cargo build -p no-std-check
+//! ```
+//!
+//! Building it together with the other crates of the workspace enables the
+//! engine's default features for all of them, so it has to be built on its
+//! own. `cargo test -p no-std-check` additionally runs the search.
+
+#![cfg_attr(not(test), no_std)]
+
+use connect4::{find_next_move, Eval, NextMove, State};
+
+/// Shallow serial search in a position where column 3 wins immediately
+pub fn search_smoke_test() -> (NextMove, Eval) {
+    let state = State::from_moves(&[3, 0, 3, 0, 3, 0]).unwrap();
+    find_next_move(&state, 4, u8::MAX)
+}
+
+#[test]
+fn test_search() {
+    let (moves, eval) = search_smoke_test();
+    assert_eq!(moves.into_vec(), [connect4::Column::new(3).unwrap()]);
+    assert_eq!(eval, Eval::ImmediateVictory);
+}
@@ -0,0 +1,185 @@
+//! Board stored as one bitmask per player, which makes dropping a piece and
+//! checking for a win a handful of bit operations.
+//!
+//! Bit `column * HEIGHT + height` is the cell `height` rows above the bottom
+//! of `column`. The topmost bit of each column is always empty, so shifted
+//! lines can't wrap around from one column into the next.
+
+use crate::{Cell, Column, MoveResult, Player, State, COLUMNS, ROWS};
+
+/// Bits per column, including the empty one on top
+const HEIGHT: u8 = ROWS + 1;
+
+const _: () = assert!(COLUMNS as u32 * HEIGHT as u32 <= u64::BITS);
+
+/// Alternative representation of a `State`, convertible in both directions
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct BitBoard {
+    /// Pieces of each player, indexed by `Player as usize`
+    pub(crate) pieces: [u64; 2],
+    /// Blocked cells, see `Cell::Blocked`
+    pub(crate) blocked: u64,
+    pub(crate) player: Player,
+    pub(crate) win_length: u8,
+}
+
+impl BitBoard {
+    pub fn current_player(&self) -> Player {
+        self.player
+    }
+
+    /// Same result as `State::try_move`
+    pub fn try_move(&self, column: Column) -> MoveResult<Self> {
+        let column = u8::from(column);
+        let height = self.height(column);
+        if height == ROWS {
+            return MoveResult::Impossible;
+        }
+
+        let bit = Self::bit(column, height);
+        if self.completes_line(self.player, bit) {
+            return MoveResult::Victory;
+        }
+        let mut next = *self;
+        next.pieces[self.player as usize] |= bit;
+        next.player = self.player.other();
+        MoveResult::State(next)
+    }
+
+    /// Same as `State::mirrored`
+    pub fn mirrored(&self) -> Self {
+        let mirror = |bitmask: u64| {
+            let mut mirrored = 0;
+            for column in 0..COLUMNS {
+                let bits = (bitmask & Self::column_mask(column)) >> (column * HEIGHT);
+                mirrored |= bits << ((COLUMNS - 1 - column) * HEIGHT);
+            }
+            mirrored
+        };
+        Self {
+            pieces: [mirror(self.pieces[0]), mirror(self.pieces[1])],
+            blocked: mirror(self.blocked),
+            ..*self
+        }
+    }
+
+    /// Number of pieces and blocked cells in `column`
+    pub(crate) fn height(&self, column: u8) -> u8 {
+        ((self.pieces[0] | self.pieces[1] | self.blocked) & Self::column_mask(column)).count_ones()
+            as u8
+    }
+
+    /// Bit of the cell `height` rows above the bottom of `column`
+    pub(crate) fn bit(column: u8, height: u8) -> u64 {
+        1 << (column * HEIGHT + height)
+    }
+
+    /// Whether a piece of `player` at `bit` would complete a line
+    pub(crate) fn completes_line(&self, player: Player, bit: u64) -> bool {
+        has_line(self.pieces[player as usize] | bit, self.win_length)
+    }
+
+    /// Bits of all cells of `column`
+    pub(crate) fn column_mask(column: u8) -> u64 {
+        ((1 << ROWS) - 1) << (column * HEIGHT)
+    }
+}
+
+/// Whether `pieces` contain `length` in a row in any direction
+fn has_line(pieces: u64, length: u8) -> bool {
+    // Vertical, diagonal (down), horizontal, diagonal (up)
+    [1, HEIGHT - 1, HEIGHT, HEIGHT + 1].iter().any(|&step| {
+        // Pieces that are the start of a line of the given length
+        let mut starts = pieces;
+        for offset in 1..length {
+            let shift = u32::from(step) * u32::from(offset);
+            starts &= pieces.checked_shr(shift).unwrap_or(0);
+        }
+        starts != 0
+    })
+}
+
+impl From<State> for BitBoard {
+    fn from(state: State) -> Self {
+        let (mut pieces, mut blocked) = ([0; 2], 0);
+        for (column, row, cell) in state.cells() {
+            let bit = Self::bit(column, ROWS - 1 - row);
+            match cell {
+                Cell::Set(player) => pieces[player as usize] |= bit,
+                Cell::Blocked => blocked |= bit,
+                Cell::Empty => (),
+            }
+        }
+        Self {
+            pieces,
+            blocked,
+            player: state.player,
+            win_length: state.win_length,
+        }
+    }
+}
+
+impl From<BitBoard> for State {
+    fn from(bitboard: BitBoard) -> Self {
+        let mut board = [[Cell::Empty; COLUMNS as usize]; ROWS as usize];
+        for row in 0..ROWS {
+            for column in 0..COLUMNS {
+                let bit = BitBoard::bit(column, ROWS - 1 - row);
+                for &player in &[Player::O, Player::X] {
+                    if bitboard.pieces[player as usize] & bit != 0 {
+                        board[row as usize][column as usize] = Cell::Set(player);
+                    }
+                }
+                if bitboard.blocked & bit != 0 {
+                    board[row as usize][column as usize] = Cell::Blocked;
+                }
+            }
+        }
+        State {
+            win_length: bitboard.win_length,
+            ..State::from_board(board, bitboard.player)
+        }
+    }
+}
+
+#[test]
+fn test_matches_state() {
+    for seed in 0..200 {
+        let mut rng = oorandom::Rand32::new(seed);
+        let win_length = [4, 4, 3, 5][seed as usize % 4];
+        let mut state = State::with_win_length(Player::O, win_length);
+        for column in 0..seed as u8 % 3 {
+            state.block_cell(column * 3).unwrap();
+        }
+        loop {
+            let bitboard = BitBoard::from(state);
+            assert_eq!(State::from(bitboard), state);
+            let weights = crate::EvalWeights {
+                threats: 4,
+                ..Default::default()
+            };
+            assert_eq!(
+                bitboard.heuristic_score(&weights),
+                state.heuristic_score(&weights)
+            );
+            assert_eq!(bitboard.mirrored(), BitBoard::from(state.mirrored()));
+            for column in (0..COLUMNS).map(Column) {
+                let expected = match state.try_move(column) {
+                    MoveResult::Impossible => MoveResult::Impossible,
+                    MoveResult::Victory => MoveResult::Victory,
+                    MoveResult::State(next) => MoveResult::State(BitBoard::from(next)),
+                };
+                assert_eq!(bitboard.try_move(column), expected);
+            }
+
+            let moves: Vec<_> = state.legal_moves().collect();
+            if moves.is_empty() {
+                break;
+            }
+            match state.try_move(moves[rng.rand_range(0..moves.len() as u32) as usize]) {
+                MoveResult::State(next) => state = next,
+                _ => break,
+            }
+        }
+    }
+}
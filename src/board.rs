@@ -0,0 +1,231 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::Display,
+    ops::{Index, IndexMut},
+};
+
+use crate::{Cell, Player, COLUMNS, ROWS};
+
+/// The cells of a board on their own, without whose turn it is or how many
+/// pieces in a row win, e.g. to look at a finished game. Every `State` has one.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Board(pub(crate) [[Cell; COLUMNS as usize]; ROWS as usize]);
+
+impl core::fmt::Debug for Board {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "{}", self.print_board())
+    }
+}
+
+/// Board with column numbers above it, for players
+impl core::fmt::Display for Board {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.render_with(|column, row| self[(column, row)].glyph()))
+    }
+}
+
+/// Rows from the top, each from left to right
+impl From<[[Cell; COLUMNS as usize]; ROWS as usize]> for Board {
+    fn from(grid: [[Cell; COLUMNS as usize]; ROWS as usize]) -> Self {
+        Self(grid)
+    }
+}
+
+/// Column (left to right), then Row (top to bottom)
+impl Index<(u8, u8)> for Board {
+    type Output = Cell;
+
+    fn index(&self, index: (u8, u8)) -> &Self::Output {
+        &self.0[index.1 as usize][index.0 as usize]
+    }
+}
+
+impl IndexMut<(u8, u8)> for Board {
+    fn index_mut(&mut self, index: (u8, u8)) -> &mut Self::Output {
+        &mut self.0[index.1 as usize][index.0 as usize]
+    }
+}
+
+impl Board {
+    /// Every cell as (column, row, cell), row by row from the top and each
+    /// row from left to right, i.e. in reading order of `print_board`
+    pub fn cells(&self) -> impl Iterator<Item = (u8, u8, Cell)> + '_ {
+        (0..ROWS)
+            .flat_map(move |row| (0..COLUMNS).map(move |column| (column, row, self[(column, row)])))
+    }
+
+    /// Number of pieces of `Player::O` and of `Player::X`
+    pub fn count_pieces(&self) -> (usize, usize) {
+        self.cells().fold((0, 0), |(o, x), (.., cell)| match cell {
+            Cell::Set(Player::O) => (o + 1, x),
+            Cell::Set(Player::X) => (o, x + 1),
+            Cell::Empty | Cell::Blocked => (o, x),
+        })
+    }
+
+    /// One line per row, with the symbols of `Cell::symbol`
+    pub fn print_board(&self) -> String {
+        let mut out = String::new();
+        for row in &self.0 {
+            for cell in row {
+                out.push(cell.symbol())
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The layout of `Display`: column numbers above the rows, with each
+    /// cell drawn as `cell(column, row)`
+    pub fn render_with<T: Display>(&self, mut cell: impl FnMut(u8, u8) -> T) -> String {
+        let header: Vec<_> = (0..COLUMNS).map(|column| column.to_string()).collect();
+        let mut out = header.join(" ");
+        out.push('\n');
+        for row in 0..ROWS {
+            let cells: Vec<_> = (0..COLUMNS)
+                .map(|column| cell(column, row).to_string())
+                .collect();
+            out.push_str(&cells.join(" "));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Whether every cell is taken
+    pub fn is_full(&self) -> bool {
+        self.0[0].iter().all(|&cell| cell != Cell::Empty)
+    }
+
+    /// The same board with the columns in reverse order
+    pub fn mirrored(&self) -> Self {
+        let mut board = *self;
+        for row in &mut board.0 {
+            row.reverse();
+        }
+        board
+    }
+
+    /// Player with `win_length` pieces in a row anywhere on the board.
+    /// If both players have one, which can't happen in a game, returns either.
+    pub fn winner(&self, win_length: u8) -> Option<Player> {
+        self.winning_lines(win_length)
+            .next()
+            .map(|(player, ..)| player)
+    }
+
+    /// Cells (column, row) of `win_length` pieces in a row, in order along the line.
+    /// If there are several such lines, returns any one of them.
+    pub fn winning_cells(&self, win_length: u8) -> Option<Vec<(u8, u8)>> {
+        self.winning_lines(win_length)
+            .next()
+            .map(|(_, column, row, (column_step, row_step))| {
+                (0..win_length as i16)
+                    .map(|offset| {
+                        (
+                            (column as i16 + offset * column_step) as u8,
+                            (row as i16 + offset * row_step) as u8,
+                        )
+                    })
+                    .collect()
+            })
+    }
+
+    /// Owner, start cell and direction of every line of `win_length` pieces,
+    /// lines longer than that once for every start cell
+    pub(crate) fn winning_lines(
+        &self,
+        win_length: u8,
+    ) -> impl Iterator<Item = (Player, u8, u8, (i16, i16))> + '_ {
+        self.cells()
+            .filter_map(|(column, row, cell)| match cell {
+                Cell::Set(player) => Some((player, column, row)),
+                Cell::Empty | Cell::Blocked => None,
+            })
+            .flat_map(move |(player, column, row)| {
+                IntoIterator::into_iter([(1, 0), (0, 1), (1, 1), (1, -1)])
+                    .filter(move |&(column_step, row_step)| {
+                        (0..win_length as i16).all(|offset| {
+                            let column = column as i16 + offset * column_step;
+                            let row = row as i16 + offset * row_step;
+                            (0..COLUMNS as i16).contains(&column)
+                                && (0..ROWS as i16).contains(&row)
+                                && self[(column as u8, row as u8)] == Cell::Set(player)
+                        })
+                    })
+                    .map(move |step| (player, column, row, step))
+            })
+    }
+
+    /// Cell (column, row) of a piece with an empty cell below it
+    pub(crate) fn floating_piece(&self) -> Option<(u8, u8)> {
+        for column in 0..COLUMNS {
+            for row in 0..ROWS - 1 {
+                if self[(column, row)] != Cell::Empty && self[(column, row + 1)] == Cell::Empty {
+                    return Some((column, row));
+                }
+            }
+        }
+        None
+    }
+
+    /// Number of pieces and blocked cells in each column
+    pub(crate) fn heights(&self) -> [u8; COLUMNS as usize] {
+        let mut heights = [0; COLUMNS as usize];
+        for (column, _, cell) in self.cells() {
+            if cell != Cell::Empty {
+                heights[column as usize] += 1;
+            }
+        }
+        heights
+    }
+}
+
+#[test]
+fn test_board_matches_state() {
+    use crate::{drawn_board, State, WINNING_LENGTH};
+
+    // O wins with the last move
+    let columns = [3, 3, 2, 4, 3, 2, 4, 4, 2, 6, 6, 5, 0, 0, 0, 0, 0, 0, 1];
+    let mut states: Vec<_> = (0..=columns.len())
+        .map(|moves| State::from_moves(&columns[..moves]).unwrap())
+        .collect();
+    states.push(drawn_board());
+    let won = states[columns.len()];
+
+    for state in &states {
+        let board = state.board();
+        assert!(board.cells().eq(state.cells()));
+        assert_eq!(board.count_pieces(), state.count_pieces());
+        assert_eq!(board.print_board(), state.print_board());
+        assert_eq!(board.to_string(), state.to_string());
+        assert_eq!(board.winner(WINNING_LENGTH), state.winner());
+        assert_eq!(board.winning_cells(WINNING_LENGTH), state.winning_cells());
+        assert_eq!(board.is_full(), state.is_full());
+        assert_eq!(
+            board
+                .heights()
+                .iter()
+                .map(|&height| height as usize)
+                .sum::<usize>(),
+            state.turn()
+        );
+        assert_eq!(board.mirrored(), *state.mirrored().board());
+        assert_eq!(board.mirrored().mirrored(), *board);
+        for (column, row, cell) in board.cells() {
+            assert_eq!(board[(column, row)], state[(column, row)]);
+            assert_eq!(cell, state[(column, row)]);
+        }
+    }
+    assert_eq!(won.board().winner(WINNING_LENGTH), Some(Player::O));
+    assert_eq!(won.board().winner(ROWS + 1), None);
+
+    let mut board = Board::default();
+    board[(3, ROWS - 1)] = Cell::Set(Player::X);
+    assert_eq!(board.count_pieces(), (0, 1));
+    assert_eq!(board.floating_piece(), None);
+    board[(3, 0)] = Cell::Blocked;
+    assert_eq!(board.floating_piece(), Some((3, 0)));
+}
@@ -0,0 +1,184 @@
+//! Opening book: replies for known positions of the standard game, so the
+//! engine doesn't have to search them again every game.
+//!
+//! The text format has one position per line, given by the moves leading
+//! to it and followed by the reply. Empty lines and lines starting with `#`
+//! are ignored.
+//!
+//! ```text
+//! # Center first
+//!  -> 3
+//! 3 -> 3
+//! 33 -> 3
+//! ```
+
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::{
+    search::Position, table::HashMap, Column, MoveResult, SequenceError, State, WINNING_LENGTH,
+};
+
+/// Why a text can't be parsed as an `OpeningBook`.
+/// Lines are numbered from 0.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BookError {
+    /// The line isn't of the form `moves -> reply`
+    InvalidLine(usize),
+    /// The moves on the line can't be played
+    InvalidMoves(usize, SequenceError),
+    /// The reply isn't a legal move in the position
+    InvalidReply(usize),
+}
+
+impl core::fmt::Display for BookError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BookError::InvalidLine(line) => {
+                write!(f, "line {} must be of the form \"moves -> reply\"", line)
+            }
+            BookError::InvalidMoves(line, error) => write!(f, "line {}: {}", line, error),
+            BookError::InvalidReply(line) => write!(f, "line {}: reply isn't a legal move", line),
+        }
+    }
+}
+
+impl core::error::Error for BookError {}
+
+/// Replies for positions of the standard game. Mirrored positions share an
+/// entry, the reply gets mirrored accordingly.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpeningBook {
+    /// Replies by Zobrist hash of the canonical position
+    replies: HashMap<u64, Column>,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the text format described in the module documentation
+    pub fn parse(text: &str) -> Result<Self, BookError> {
+        let mut book = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (moves, reply) = line.split_once("->").ok_or(BookError::InvalidLine(index))?;
+            let state = State::from_moves_str(moves.trim())
+                .map_err(|error| BookError::InvalidMoves(index, error))?;
+            let reply = reply
+                .trim()
+                .parse()
+                .ok()
+                .and_then(Column::new)
+                .ok_or(BookError::InvalidReply(index))?;
+            if state.is_terminal() || state.try_move(reply) == MoveResult::Impossible {
+                return Err(BookError::InvalidReply(index));
+            }
+            book.insert(&state, reply);
+        }
+        Ok(book)
+    }
+
+    /// Reads and parses a book file
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Sets the reply for `state` and its mirror image
+    pub fn insert(&mut self, state: &State, reply: Column) {
+        let (key, mirrored) = state.canonical();
+        let reply = if mirrored {
+            State::mirror_move(reply)
+        } else {
+            reply
+        };
+        self.replies.insert(key.hash, reply);
+    }
+
+    /// The reply for `state`, if it is in the book. Positions with a
+    /// different winning length are never in the book.
+    pub fn get(&self, state: &State) -> Option<Column> {
+        if state.win_length != WINNING_LENGTH {
+            return None;
+        }
+        let (key, mirrored) = state.canonical();
+        let reply = *self.replies.get(&key.hash)?;
+        Some(if mirrored {
+            State::mirror_move(reply)
+        } else {
+            reply
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.replies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replies.is_empty()
+    }
+}
+
+#[test]
+fn test_parse() {
+    use crate::Player;
+
+    let book = OpeningBook::parse(
+        "# Comment\n\
+         \n\
+         -> 3\n\
+         3 -> 3\n\
+         2 -> 1\n",
+    )
+    .unwrap();
+    assert_eq!(book.len(), 3);
+    assert_eq!(book.get(&State::empty()), Some(Column(3)));
+    assert_eq!(book.get(&State::from_moves(&[3]).unwrap()), Some(Column(3)));
+    assert_eq!(book.get(&State::from_moves(&[2]).unwrap()), Some(Column(1)));
+    // Mirror image of the position after 2
+    assert_eq!(book.get(&State::from_moves(&[4]).unwrap()), Some(Column(5)));
+    assert_eq!(book.get(&State::from_moves(&[0]).unwrap()), None);
+    assert_eq!(book.get(&State::with_win_length(Player::O, 5)), None);
+
+    assert_eq!(OpeningBook::parse("33 3"), Err(BookError::InvalidLine(0)));
+    assert_eq!(
+        OpeningBook::parse("\n3a -> 3"),
+        Err(BookError::InvalidMoves(
+            1,
+            SequenceError::InvalidCharacter(1)
+        ))
+    );
+    assert_eq!(
+        OpeningBook::parse("000000 -> 0"),
+        Err(BookError::InvalidReply(0))
+    );
+    assert_eq!(
+        OpeningBook::parse("3 -> 7"),
+        Err(BookError::InvalidReply(0))
+    );
+}
+
+#[test]
+fn test_book_move_skips_search() {
+    use crate::find_next_move_with_book;
+
+    let book = OpeningBook::parse("33 -> 2").unwrap();
+    let state = State::from_moves(&[3, 3]).unwrap();
+    let (moves, eval, stats) = find_next_move_with_book(&state, &book, 6, u8::MAX);
+    assert_eq!(moves.into_vec(), [Column(2)]);
+    assert_eq!(eval, None);
+    assert_eq!(stats.nodes, 0);
+
+    let state = State::from_moves(&[3, 2]).unwrap();
+    let (moves, eval, stats) = find_next_move_with_book(&state, &book, 4, u8::MAX);
+    assert_eq!(
+        (moves, eval.unwrap()),
+        crate::find_next_move(&state, 4, u8::MAX)
+    );
+    assert!(stats.nodes > 0);
+}
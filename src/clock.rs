@@ -0,0 +1,87 @@
+//! Where searches with a time budget get the time from, so tests can
+//! control it instead of depending on how fast the machine is
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Source of the time for searches with a time budget
+pub trait Clock: Sync {
+    /// Time passed since some fixed point, e.g. the creation of the clock.
+    /// Must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+/// The real time, since the clock was created
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that stands still until it's advanced, e.g. by a test between the
+/// steps of a search
+#[derive(Debug, Default)]
+pub struct MockClock {
+    nanos: AtomicU64,
+}
+
+impl MockClock {
+    /// Clock at time zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.nanos
+            .fetch_add(by.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+#[test]
+fn test_mock_clock() {
+    let clock = MockClock::new();
+    assert_eq!(clock.now(), Duration::ZERO);
+    clock.advance(Duration::from_millis(1500));
+    clock.advance(Duration::from_nanos(1));
+    assert_eq!(clock.now(), Duration::new(1, 500_000_001));
+
+    #[cfg(feature = "std")]
+    {
+        let clock = SystemClock::new();
+        let before = clock.now();
+        assert!(clock.now() >= before);
+    }
+}
@@ -0,0 +1,73 @@
+//! Colored terminal output using ANSI escape codes
+
+use alloc::{borrow::ToOwned, format, string::String, vec};
+
+use crate::{Cell, Player, State};
+
+const RED: &str = "31";
+const YELLOW: &str = "33";
+const BOLD_REVERSED: &str = "1;7";
+
+/// `text` with the given SGR attributes, reset afterwards
+fn paint(text: &str, attributes: &[&str]) -> String {
+    if attributes.is_empty() {
+        text.to_owned()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", attributes.join(";"), text)
+    }
+}
+
+impl State {
+    /// Same as the `Display` output, with `Player::O` in red, `Player::X` in
+    /// yellow and the top piece in `last_move` highlighted
+    pub fn render_colored(&self, last_move: Option<u8>) -> String {
+        let last_row = last_move.and_then(|column| {
+            (0..self.board.0.len()).find(|&row| self.board.0[row][column as usize] != Cell::Empty)
+        });
+
+        self.board.render_with(|column, row| {
+            let cell = self[(column, row)];
+            let mut attributes = match cell {
+                Cell::Empty => vec![],
+                Cell::Set(Player::O) => vec![RED],
+                Cell::Set(Player::X) => vec![YELLOW],
+                Cell::Blocked => vec![],
+            };
+            if last_move == Some(column) && last_row == Some(row as usize) {
+                attributes.push(BOLD_REVERSED);
+            }
+            paint(cell.glyph(), &attributes)
+        })
+    }
+}
+
+#[cfg(test)]
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[test]
+fn test_render_colored() {
+    let state = State::from_moves(&[3, 3, 2, 4, 3, 2, 4, 4, 2]).unwrap();
+    for last_move in [None, Some(2), Some(5)] {
+        let colored = state.render_colored(last_move);
+        assert!(colored.contains('\x1b'));
+        assert_eq!(strip_escapes(&colored), state.to_string());
+    }
+    assert!(state
+        .render_colored(Some(2))
+        .contains(&paint("O", &[RED, BOLD_REVERSED])));
+    assert_eq!(
+        strip_escapes(&State::empty().render_colored(None)),
+        State::empty().to_string()
+    );
+}
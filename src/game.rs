@@ -0,0 +1,473 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Cell, MoveError, Player, State, COLUMNS, ROWS};
+
+/// A game in progress: the current state and the moves that lead to it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Game {
+    start: State,
+    state: State,
+    /// Columns played, in order
+    moves: Vec<u8>,
+    winner: Option<Player>,
+    info: GameInfo,
+}
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win(Player),
+    /// The board is full without a winner
+    Draw,
+}
+
+/// Who played a game and with which engine settings, for game records
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameInfo {
+    pub player_o: String,
+    pub player_x: String,
+    /// Seed the engine picked its moves with
+    pub seed: Option<u64>,
+    /// Search depth of the engine
+    pub depth: Option<u8>,
+}
+
+/// Why a text can't be parsed as a game record. Lines and moves are numbered from 0.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecordError {
+    /// The header line isn't of the form `Key: value` with a known key and valid value
+    InvalidHeader(usize),
+    /// The header with this key is missing
+    MissingHeader(&'static str),
+    /// The move isn't a column number
+    InvalidMove(usize),
+    /// The move can't be played
+    IllegalMove(usize, MoveError),
+    /// The result doesn't match the result of playing the moves
+    WrongResult,
+}
+
+impl core::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RecordError::InvalidHeader(line) => write!(f, "line {}: invalid header", line),
+            RecordError::MissingHeader(key) => write!(f, "missing header \"{}\"", key),
+            RecordError::InvalidMove(index) => write!(f, "move {}: not a column", index),
+            RecordError::IllegalMove(index, error) => write!(f, "move {}: {}", index, error),
+            RecordError::WrongResult => write!(f, "result doesn't match the moves"),
+        }
+    }
+}
+
+impl core::error::Error for RecordError {}
+
+/// Game starting from the empty board
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(State::empty())
+    }
+}
+
+impl Game {
+    /// Game starting from `start`, which undoing can't go back past
+    pub fn new(start: State) -> Self {
+        Self {
+            start,
+            state: start,
+            moves: Vec::new(),
+            winner: None,
+            info: GameInfo::default(),
+        }
+    }
+
+    pub fn info(&self) -> &GameInfo {
+        &self.info
+    }
+
+    pub fn set_info(&mut self, info: GameInfo) {
+        self.info = info;
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn moves(&self) -> &[u8] {
+        &self.moves
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    /// How the game ended, `None` while it is still going on
+    pub fn outcome(&self) -> Option<Outcome> {
+        match self.winner {
+            Some(winner) => Some(Outcome::Win(winner)),
+            None if self.state.is_full() => Some(Outcome::Draw),
+            None => None,
+        }
+    }
+
+    /// Plays `column` for the side to move and returns the winner if it wins.
+    pub fn play(&mut self, column: u8) -> Result<Option<Player>, MoveError> {
+        if self.winner.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        self.winner = self.state.try_move_mut(column)?;
+        self.moves.push(column);
+        Ok(self.winner)
+    }
+
+    /// Takes back the last move and returns its column.
+    /// Fails without changing anything if no moves were played.
+    #[allow(clippy::result_unit_err)]
+    pub fn undo(&mut self) -> Result<u8, ()> {
+        let column = self.moves.pop().ok_or(())?;
+        // Replaying is cheap compared to a search and can't get out of sync
+        self.state = self.start;
+        for &column in &self.moves {
+            self.state.try_move_mut(column).unwrap();
+        }
+        self.winner = None;
+        Ok(column)
+    }
+
+    /// Record of the game for sharing: header lines with the players, engine
+    /// settings and result, followed by the columns played. The result is
+    /// `win-O`, `win-X`, `draw` or `*` for a game that isn't over yet.
+    /// Games that don't start from the empty board with O to move, e.g. with
+    /// blocked cells or handicap stones, have a `Start` header as well: the
+    /// side to move, then the rows from the top as by `State::print_board`,
+    /// separated by `/`.
+    ///
+    /// ```text
+    /// O: Alice
+    /// X: connect4
+    /// Seed: 1234
+    /// Depth: 8
+    /// Win length: 4
+    /// Result: win-O
+    /// 3 3 4 2 5 6 1
+    /// ```
+    pub fn to_record(&self) -> String {
+        let mut record = format!("O: {}\nX: {}\n", self.info.player_o, self.info.player_x);
+        if let Some(seed) = self.info.seed {
+            record += &format!("Seed: {}\n", seed);
+        }
+        if let Some(depth) = self.info.depth {
+            record += &format!("Depth: {}\n", depth);
+        }
+        record += &format!("Win length: {}\n", self.start.win_length());
+        if self.start != State::with_win_length(Player::O, self.start.win_length()) {
+            let rows: Vec<_> = self.start.print_board().lines().map(String::from).collect();
+            record += &format!(
+                "Start: {} {}\n",
+                self.start.current_player().symbol(),
+                rows.join("/")
+            );
+        }
+        record += &format!("Result: {}\n", self.result());
+        let moves: Vec<_> = self.moves.iter().map(|column| column.to_string()).collect();
+        record += &moves.join(" ");
+        record.push('\n');
+        record
+    }
+
+    /// Parses the format of `to_record`, checking that the moves can be
+    /// played and lead to the recorded result
+    pub fn from_record(record: &str) -> Result<Self, RecordError> {
+        let lines: Vec<_> = record.lines().collect();
+        let (moves, headers) = lines.split_last().ok_or(RecordError::MissingHeader("O"))?;
+
+        let mut info = GameInfo::default();
+        let (mut player_o, mut player_x, mut win_length, mut result) = (None, None, None, None);
+        let mut start = None;
+        for (index, line) in headers.iter().enumerate() {
+            let invalid = RecordError::InvalidHeader(index);
+            let (key, value) = line.split_once(": ").ok_or(invalid)?;
+            match key {
+                "O" => player_o = Some(value.into()),
+                "X" => player_x = Some(value.into()),
+                "Seed" => info.seed = Some(value.parse().map_err(|_| invalid)?),
+                "Depth" => info.depth = Some(value.parse().map_err(|_| invalid)?),
+                "Win length" => win_length = Some(value.parse().map_err(|_| invalid)?),
+                "Start" => start = Some((index, value)),
+                "Result" => result = Some(value),
+                _ => return Err(invalid),
+            }
+        }
+        info.player_o = player_o.ok_or(RecordError::MissingHeader("O"))?;
+        info.player_x = player_x.ok_or(RecordError::MissingHeader("X"))?;
+        let win_length = win_length.ok_or(RecordError::MissingHeader("Win length"))?;
+        let result = result.ok_or(RecordError::MissingHeader("Result"))?;
+
+        let start = match start {
+            Some((index, value)) => {
+                parse_start(value, win_length).ok_or(RecordError::InvalidHeader(index))?
+            }
+            None => State::with_win_length(Player::O, win_length),
+        };
+
+        let mut game = Game::new(start);
+        game.info = info;
+        for (index, column) in moves.split_whitespace().enumerate() {
+            let column = column
+                .parse()
+                .map_err(|_| RecordError::InvalidMove(index))?;
+            game.play(column)
+                .map_err(|error| RecordError::IllegalMove(index, error))?;
+        }
+        if game.result() != result {
+            return Err(RecordError::WrongResult);
+        }
+        Ok(game)
+    }
+
+    /// Result as written in records
+    fn result(&self) -> &'static str {
+        match self.outcome() {
+            Some(Outcome::Win(Player::O)) => "win-O",
+            Some(Outcome::Win(Player::X)) => "win-X",
+            Some(Outcome::Draw) => "draw",
+            None => "*",
+        }
+    }
+}
+
+/// Parses the value of the `Start` header of a record, checking the
+/// position as by `State::with_setup`
+fn parse_start(value: &str, win_length: u8) -> Option<State> {
+    let (player, rows) = value.split_once(' ')?;
+    let mut player = player.chars();
+    let player = match (player.next().and_then(Player::from_char), player.next()) {
+        (Some(player), None) => player,
+        _ => return None,
+    };
+    let rows: Vec<_> = rows.split('/').collect();
+    if rows.len() != ROWS as usize {
+        return None;
+    }
+    let mut grid = [[Cell::Empty; COLUMNS as usize]; ROWS as usize];
+    for (cells, row) in grid.iter_mut().zip(rows) {
+        if row.chars().count() != COLUMNS as usize {
+            return None;
+        }
+        for (cell, c) in cells.iter_mut().zip(row.chars()) {
+            *cell = Cell::from_char(c)?;
+        }
+    }
+    let mut start = State::from_board(grid, player);
+    start.win_length = win_length;
+    start.check_setup().ok()
+}
+
+#[test]
+fn test_undo() {
+    let columns = [3, 3, 2, 4, 3, 2, 4, 4, 2, 6, 6, 5, 0, 0, 0, 0, 0, 0, 1];
+    let mut game = Game::new(State::empty());
+    let mut states = Vec::new();
+    for &column in &columns {
+        states.push(*game.state());
+        game.play(column).unwrap();
+    }
+    assert_eq!(game.moves(), columns);
+    assert_eq!(game.winner(), Some(Player::O));
+    assert_eq!(game.outcome(), Some(Outcome::Win(Player::O)));
+    assert_eq!(game.play(5), Err(MoveError::GameOver));
+
+    for &column in columns.iter().rev() {
+        assert_eq!(game.undo(), Ok(column));
+        assert_eq!(*game.state(), states.pop().unwrap());
+        assert_eq!(game.winner(), None);
+        assert_eq!(game.outcome(), None);
+    }
+    assert_eq!(*game.state(), State::empty());
+    assert!(game.moves().is_empty());
+
+    assert_eq!(game.undo(), Err(()));
+    assert_eq!(game, Game::new(State::empty()));
+}
+
+#[test]
+fn test_default() {
+    assert_eq!(State::default(), State::new(Player::O));
+    let game = Game::default();
+    assert_eq!(game, Game::new(State::empty()));
+    assert!(game.moves().is_empty());
+    assert_eq!(game.outcome(), None);
+}
+
+#[test]
+fn test_outcome() {
+    let mut game = Game::new(State::empty());
+    for &column in &[3, 3, 4, 4, 5, 5] {
+        game.play(column).unwrap();
+        assert_eq!(game.outcome(), None);
+    }
+    game.play(6).unwrap();
+    assert_eq!(game.outcome(), Some(Outcome::Win(Player::O)));
+
+    let mut game = Game::new(State::empty());
+    let columns = [
+        0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 3, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4,
+        6, 5, 5, 5, 5, 5, 5, 6, 6, 6, 6, 6,
+    ];
+    let (last, columns) = columns.split_last().unwrap();
+    for &column in columns {
+        game.play(column).unwrap();
+    }
+    assert_eq!(game.outcome(), None);
+    game.play(*last).unwrap();
+    assert_eq!(*game.state(), crate::drawn_board());
+    assert_eq!(game.outcome(), Some(Outcome::Draw));
+}
+
+#[test]
+fn test_record_round_trip() {
+    let mut rng = oorandom::Rand32::new(7);
+    let mut game = Game::new(State::empty());
+    game.set_info(GameInfo {
+        player_o: "connect4".into(),
+        player_x: "connect4 (again)".into(),
+        seed: Some(7),
+        depth: Some(3),
+    });
+    while game.outcome().is_none() {
+        let (moves, _) = crate::find_next_move(game.state(), 3, u8::MAX);
+        game.play(moves[rng.rand_range(0..moves.len() as u32) as usize].into())
+            .unwrap();
+    }
+
+    let record = game.to_record();
+    assert!(record.starts_with("O: connect4\nX: connect4 (again)\nSeed: 7\nDepth: 3\n"));
+    assert!(record.contains("\nResult: win-") || record.contains("\nResult: draw\n"));
+    assert_eq!(Game::from_record(&record), Ok(game));
+
+    let unfinished = Game::new(State::empty());
+    let record = unfinished.to_record();
+    assert_eq!(record, "O: \nX: \nWin length: 4\nResult: *\n\n");
+    assert_eq!(Game::from_record(&record), Ok(unfinished));
+
+    // X moves first and wins
+    let mut game = Game::new(State::new(Player::X));
+    for &column in &[0, 1, 0, 1, 0, 1, 0] {
+        game.play(column).unwrap();
+    }
+    assert_eq!(game.winner(), Some(Player::X));
+    let record = game.to_record();
+    assert!(record.contains("\nStart: x ......./"));
+    assert_eq!(Game::from_record(&record), Ok(game));
+
+    // Smaller board, with a blocked cell and a handicap stone
+    let mut start = State::with_setup(&[(3, Player::O)], Player::X).unwrap();
+    start.block_cell(6).unwrap();
+    let mut game = Game::new(start);
+    for &column in &[6, 3, 2] {
+        game.play(column).unwrap();
+    }
+    let record = game.to_record();
+    assert!(record.ends_with(
+        "Win length: 4\nStart: x ......./......./......./......./......./...o..#\nResult: *\n6 3 2\n"
+    ));
+    let replayed = Game::from_record(&record).unwrap();
+    assert_eq!(replayed, game);
+    assert_eq!(replayed.state()[(6, ROWS - 2)], Cell::Set(Player::X));
+}
+
+#[test]
+fn test_invalid_records() {
+    let header = "O: a\nX: b\nWin length: 4\n";
+    assert_eq!(
+        Game::from_record(&format!("{}Result: win-X\n0 1 0 1 0 1 0\n", header)),
+        Err(RecordError::WrongResult)
+    );
+    assert_eq!(
+        Game::from_record(&format!("{}Result: *\n0 1 0 1 0 1 0\n", header)),
+        Err(RecordError::WrongResult)
+    );
+    assert!(Game::from_record(&format!("{}Result: win-O\n0 1 0 1 0 1 0\n", header)).is_ok());
+    assert_eq!(
+        Game::from_record(&format!("{}Result: *\n0 1 0 1 0 1 0 1\n", header)),
+        Err(RecordError::IllegalMove(7, MoveError::GameOver))
+    );
+    assert_eq!(
+        Game::from_record(&format!("{}Result: *\n0 x\n", header)),
+        Err(RecordError::InvalidMove(1))
+    );
+    assert_eq!(
+        Game::from_record("O: a\nX b\nResult: *\n\n"),
+        Err(RecordError::InvalidHeader(1))
+    );
+    for start in [
+        "x .......",
+        "xo ......./......./......./......./......./.......",
+        "x ......./......./......./......./......./......?",
+        // A floating piece
+        "x ......./......./......./......./...o.../.......",
+    ] {
+        assert_eq!(
+            Game::from_record(&format!("{}Start: {}\nResult: *\n\n", header, start)),
+            Err(RecordError::InvalidHeader(3))
+        );
+    }
+    assert_eq!(
+        Game::from_record("O: a\nWin length: 4\nResult: *\n\n"),
+        Err(RecordError::MissingHeader("X"))
+    );
+}
+
+#[test]
+fn test_table_across_undo() {
+    use crate::{find_next_move_with_table, MoveResult, Position, TranspositionTable};
+
+    // One table for the whole game, as when analyzing it move by move
+    let mut table = TranspositionTable::default();
+    let mut game = Game::new(State::empty());
+    for &column in &[3, 3, 2, 4] {
+        game.play(column).unwrap();
+    }
+    let position = *game.state();
+    let fresh = find_next_move_with_table(&position, 5, &mut TranspositionTable::default());
+    assert_eq!(find_next_move_with_table(&position, 5, &mut table), fresh);
+
+    // Continuations searched deeper than the position itself fill the table
+    // with entries for the positions after it
+    for continuation in [&[2, 2, 4][..], &[1, 5, 5, 1], &[4, 0]] {
+        for &column in continuation {
+            game.play(column).unwrap();
+            find_next_move_with_table(game.state(), 7, &mut table);
+        }
+        for _ in continuation {
+            game.undo().unwrap();
+        }
+        assert_eq!(*game.state(), position);
+        assert_eq!(find_next_move_with_table(&position, 5, &mut table), fresh);
+    }
+
+    // The same position after a different move order and mirrored
+    let transposed = State::from_moves(&[2, 4, 3, 3]).unwrap();
+    assert_eq!(transposed, position);
+    assert_eq!(find_next_move_with_table(&transposed, 5, &mut table), fresh);
+    let (next_moves, eval) = find_next_move_with_table(&position.mirrored(), 5, &mut table);
+    assert_eq!(eval, fresh.1);
+    assert!(next_moves
+        .iter()
+        .copied()
+        .eq(fresh.0.iter().map(|&m| State::mirror_move(m))));
+    assert!(!position.is_symmetric());
+
+    // Tables warmed up by a deeper search after each move, before the
+    // position itself is searched at all
+    for next in position.legal_moves() {
+        let mut table = TranspositionTable::default();
+        if let MoveResult::State(child) = position.try_move(next) {
+            find_next_move_with_table(&child, 7, &mut table);
+        }
+        assert_eq!(find_next_move_with_table(&position, 5, &mut table), fresh);
+    }
+}
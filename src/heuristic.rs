@@ -0,0 +1,308 @@
+use crate::{
+    state3d::{LINES, SIZE_3D},
+    BitBoard, Cell, State, State3D, COLUMNS, ROWS,
+};
+
+/// How much each feature of a position counts towards its heuristic score,
+/// so the evaluation can be tuned at runtime. Features of the opponent count
+/// negatively.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalWeights {
+    /// Value of a line with one piece missing for a win
+    pub three: i32,
+    /// Value of a line with two pieces missing for a win
+    pub two: i32,
+    /// Value of a piece in the center column
+    pub center: i32,
+    /// Value of an empty cell that would complete a line, whether or not
+    /// it can be played yet
+    pub threats: i32,
+}
+
+impl Default for EvalWeights {
+    /// The weights the engine plays with. Threats are already covered by
+    /// the lines, so they don't count extra.
+    fn default() -> Self {
+        Self {
+            three: 5,
+            two: 2,
+            center: 3,
+            threats: 0,
+        }
+    }
+}
+
+impl EvalWeights {
+    /// Value of a line that contains `pieces` of a single player
+    fn line_value(&self, pieces: u8, win_length: u8) -> i32 {
+        if pieces + 1 == win_length {
+            self.three
+        } else if pieces + 2 == win_length {
+            self.two
+        } else {
+            0
+        }
+    }
+}
+
+impl State {
+    /// Estimates how promising a non-terminal position is for the side to
+    /// move by counting lines that can still be completed, pieces in the
+    /// center column and threats. Positive if the side to move is better off.
+    pub fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+        let mut score = 0;
+
+        for &(column_step, row_step) in &[(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for row in 0..ROWS as i16 {
+                for column in 0..COLUMNS as i16 {
+                    let last_column = column + column_step * (self.win_length as i16 - 1);
+                    let last_row = row + row_step * (self.win_length as i16 - 1);
+                    if !(0..COLUMNS as i16).contains(&last_column)
+                        || !(0..ROWS as i16).contains(&last_row)
+                    {
+                        continue;
+                    }
+
+                    let (mut own, mut other, mut blocked) = (0, 0, false);
+                    for offset in 0..self.win_length as i16 {
+                        let cell = self[(
+                            (column + offset * column_step) as u8,
+                            (row + offset * row_step) as u8,
+                        )];
+                        match cell {
+                            Cell::Set(player) if player == self.player => own += 1,
+                            Cell::Set(_) => other += 1,
+                            Cell::Empty => (),
+                            Cell::Blocked => blocked = true,
+                        }
+                    }
+                    if blocked {
+                        continue;
+                    }
+                    score += match (own, other) {
+                        (0, 0) => 0,
+                        (pieces, 0) => weights.line_value(pieces, self.win_length),
+                        (0, pieces) => -weights.line_value(pieces, self.win_length),
+                        _ => 0,
+                    };
+                }
+            }
+        }
+
+        for row in 0..ROWS {
+            match self[(COLUMNS / 2, row)] {
+                Cell::Set(player) if player == self.player => score += weights.center,
+                Cell::Set(_) => score -= weights.center,
+                Cell::Empty | Cell::Blocked => (),
+            }
+        }
+
+        if weights.threats != 0 {
+            for (column, row, cell) in self.cells() {
+                if cell != Cell::Empty {
+                    continue;
+                }
+                if self.check_win_through(column, row, self.player) {
+                    score += weights.threats;
+                }
+                if self.check_win_through(column, row, self.player.other()) {
+                    score -= weights.threats;
+                }
+            }
+        }
+
+        score
+    }
+}
+
+impl BitBoard {
+    /// Same as `State::heuristic_score`
+    pub fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+        let own = self.pieces[self.player as usize];
+        let other = self.pieces[self.player.other() as usize];
+        let length = self.win_length as i16;
+        let mut score = 0;
+
+        for &(column_step, height_step) in &[(1, 0), (0, 1), (1, 1), (1, -1)] {
+            for height in 0..ROWS as i16 {
+                for column in 0..COLUMNS as i16 {
+                    let last_column = column + column_step * (length - 1);
+                    let last_height = height + height_step * (length - 1);
+                    if !(0..COLUMNS as i16).contains(&last_column)
+                        || !(0..ROWS as i16).contains(&last_height)
+                    {
+                        continue;
+                    }
+
+                    let mut line = 0;
+                    for offset in 0..length {
+                        line |= BitBoard::bit(
+                            (column + offset * column_step) as u8,
+                            (height + offset * height_step) as u8,
+                        );
+                    }
+                    if self.blocked & line != 0 {
+                        continue;
+                    }
+                    score += match ((own & line).count_ones(), (other & line).count_ones()) {
+                        (0, 0) => 0,
+                        (pieces, 0) => weights.line_value(pieces as u8, self.win_length),
+                        (0, pieces) => -weights.line_value(pieces as u8, self.win_length),
+                        _ => 0,
+                    };
+                }
+            }
+        }
+
+        let center = BitBoard::column_mask(COLUMNS / 2);
+        score += weights.center * (own & center).count_ones() as i32;
+        score -= weights.center * (other & center).count_ones() as i32;
+
+        if weights.threats != 0 {
+            let occupied = own | other | self.blocked;
+            for column in 0..COLUMNS {
+                for height in 0..ROWS {
+                    let bit = BitBoard::bit(column, height);
+                    if occupied & bit != 0 {
+                        continue;
+                    }
+                    if self.completes_line(self.player, bit) {
+                        score += weights.threats;
+                    }
+                    if self.completes_line(self.player.other(), bit) {
+                        score -= weights.threats;
+                    }
+                }
+            }
+        }
+
+        score
+    }
+}
+
+impl State3D {
+    /// Counts lines that can still be completed, like `State::heuristic_score`.
+    /// Only the weights of lines apply.
+    pub fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+        let own = self.pieces[self.player as usize];
+        let other = self.pieces[self.player.other() as usize];
+        LINES
+            .iter()
+            .map(
+                |&line| match ((own & line).count_ones(), (other & line).count_ones()) {
+                    (0, 0) => 0,
+                    (pieces, 0) => weights.line_value(pieces as u8, SIZE_3D),
+                    (0, pieces) => -weights.line_value(pieces as u8, SIZE_3D),
+                    _ => 0,
+                },
+            )
+            .sum()
+    }
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_center_preferred() {
+    use crate::{Cell::*, Player::*};
+
+    let center = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty],
+        ],
+        O,
+    );
+    let edge = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        ],
+        O,
+    );
+    assert!(center.heuristic_score(&EvalWeights::default()) > edge.heuristic_score(&EvalWeights::default()));
+    assert!(edge.heuristic_score(&EvalWeights::default()) > 0);
+    assert_eq!(State::empty().heuristic_score(&EvalWeights::default()), 0);
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_live_three_preferred() {
+    use crate::{Cell::*, Player::*};
+
+    let live = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Set(X)],
+            [Empty,  Set(O), Set(O), Set(O), Empty,  Empty,  Set(X)],
+        ],
+        O,
+    );
+    let blocked = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(X), Set(O), Set(O), Set(O), Set(X), Empty,  Empty],
+        ],
+        O,
+    );
+    assert!(live.heuristic_score(&EvalWeights::default()) > blocked.heuristic_score(&EvalWeights::default()));
+}
+
+#[test]
+fn test_weights() {
+    let baseline = EvalWeights::default();
+    let doubled_center = EvalWeights {
+        center: 2 * baseline.center,
+        ..baseline
+    };
+
+    // O to move with two pieces in the center column, X with none
+    let center = State::from_moves(&[3, 0, 3, 6]).unwrap();
+    assert!(center.heuristic_score(&doubled_center) > center.heuristic_score(&baseline));
+    let edge = State::from_moves(&[0, 6, 0, 6]).unwrap();
+    assert_eq!(
+        edge.heuristic_score(&doubled_center),
+        edge.heuristic_score(&baseline)
+    );
+
+    // O could complete the bottom row in column 3, X has no such cell
+    let threat = State::from_moves(&[0, 0, 1, 1, 2, 6]).unwrap();
+    let threats = EvalWeights {
+        threats: 10,
+        ..baseline
+    };
+    assert_eq!(
+        threat.heuristic_score(&threats),
+        threat.heuristic_score(&baseline) + 10
+    );
+
+    // The search ends on the heuristic, so it picks up the weights
+    use crate::{find_next_move, find_next_move_with_weights, Column, Eval};
+    assert_eq!(
+        find_next_move_with_weights(&center, 2, u8::MAX, &baseline),
+        find_next_move(&center, 2, u8::MAX)
+    );
+    let only_center = EvalWeights {
+        three: 0,
+        two: 0,
+        center: 1,
+        threats: 0,
+    };
+    let (moves, eval) = find_next_move_with_weights(&State::empty(), 0, u8::MAX, &only_center);
+    assert_eq!(moves[..], [Column(3)]);
+    assert_eq!(eval, Eval::Neutral(1));
+}
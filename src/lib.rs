@@ -1,13 +1,129 @@
-use std::ops::{Index, IndexMut};
+//! Connect 4 engine: board representation, move rules and a minimax search.
+//!
+//! Without the default `std` feature, the library only needs `alloc`.
+//! Loading files and parallel search (the `parallel` feature) are left out
+//! then, and time limits need a `Clock` passed in. Without `parallel`,
+//! every search runs on the calling thread and rayon isn't needed. The
+//! default `cli` feature only adds the dependencies of the binary.
 
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
-use smallvec::{smallvec, SmallVec};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-const COLUMNS: u8 = 7;
-const ROWS: u8 = 6;
-const WINNING_LENGTH: u8 = 4;
+extern crate alloc;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+use alloc::{string::String, vec::Vec};
+use core::{
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+    ops::Index,
+};
+
+mod bitboard;
+mod board;
+mod book;
+mod clock;
+#[cfg(feature = "color")]
+mod color;
+mod game;
+mod heuristic;
+#[cfg(feature = "std")]
+mod mcts;
+mod perft;
+mod popout;
+mod review;
+mod rollout;
+mod search;
+#[cfg(feature = "std")]
+mod select;
+mod selfplay;
+#[cfg(feature = "serde")]
+mod serialize;
+mod state3d;
+mod table;
+mod text;
+mod threats;
+mod tournament;
+mod trace;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod zobrist;
+
+pub use bitboard::BitBoard;
+pub use board::Board;
+pub use book::{BookError, OpeningBook};
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+pub use clock::{Clock, MockClock};
+pub use game::{Game, GameInfo, Outcome, RecordError};
+pub use heuristic::EvalWeights;
+#[cfg(feature = "std")]
+pub use mcts::mcts_best_move;
+pub use perft::{perft, positions_at_ply};
+pub use popout::PopMove;
+pub use review::{analyze_game, analyze_game_from, MoveAnalysis};
+pub use rollout::RngLike;
+#[cfg(feature = "std")]
+pub use search::find_best_move_timed;
+pub use search::{
+    evaluate_batch, find_best_move_timed_with_clock, find_next_move, find_next_move_bitboard,
+    find_next_move_cancellable, find_next_move_with_book, find_next_move_with_stats,
+    find_next_move_with_table, find_next_move_with_weights, find_principal_variation, rank_moves,
+    search, search_with_clock, search_with_progress, Eval, NextMove, Position, SearchConfig,
+    SearchStats, DEFAULT_PARALLEL_UNTIL_DEPTH,
+};
+#[cfg(feature = "std")]
+pub use select::Selector;
+pub use selfplay::generate_selfplay_games;
+pub use state3d::{State3D, COLUMNS_3D, SIZE_3D};
+pub use table::TranspositionTable;
+pub use text::{parse_column, ParseError};
+pub use tournament::{run_tournament, PairingResult, TournamentResult};
+pub use trace::{trace_search, EvalTrace, MoveTrace};
+#[cfg(feature = "wasm")]
+pub use wasm::WasmGame;
+
+pub const COLUMNS: u8 = 7;
+pub const ROWS: u8 = 6;
+/// Default number of pieces in a row needed to win
+pub const WINNING_LENGTH: u8 = 4;
+
+/// Number of a column, counting from 0 on the left. Always below `COLUMNS`,
+/// so it can't be out of range or mixed up with a row.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Column(u8);
+
+impl Column {
+    /// `None` if there is no such column
+    pub fn new(column: u8) -> Option<Self> {
+        Some(Self(column)).filter(|_| column < COLUMNS)
+    }
+}
+
+impl From<Column> for u8 {
+    fn from(column: Column) -> Self {
+        column.0
+    }
+}
+
+impl TryFrom<u8> for Column {
+    type Error = MoveError;
+
+    fn try_from(column: u8) -> Result<Self, MoveError> {
+        Self::new(column).ok_or(MoveError::ColumnOutOfRange)
+    }
+}
+
+impl core::fmt::Display for Column {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum Player {
     O,
     X,
@@ -21,285 +137,1099 @@ impl Player {
             Player::O
         }
     }
+
+    /// Character used by `State::print_board` and the text format
+    pub fn symbol(self) -> char {
+        match self {
+            Player::O => 'o',
+            Player::X => 'x',
+        }
+    }
+
+    /// Inverse of `symbol`
+    pub fn from_char(c: char) -> Option<Self> {
+        [Player::O, Player::X]
+            .iter()
+            .copied()
+            .find(|player| player.symbol() == c)
+    }
+}
+
+/// Why a string isn't a `Player`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ParsePlayerError;
+
+impl core::fmt::Display for ParsePlayerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "player must be O or X, or 1 or 2 for the one moving first or second"
+        )
+    }
+}
+
+impl core::error::Error for ParsePlayerError {}
+
+impl core::str::FromStr for Player {
+    type Err = ParsePlayerError;
+
+    /// Parses the symbol in either case, or the number of the player
+    /// starting from the empty board
+    fn from_str(s: &str) -> Result<Self, ParsePlayerError> {
+        match s {
+            "o" | "O" | "1" => Ok(Player::O),
+            "x" | "X" | "2" => Ok(Player::X),
+            _ => Err(ParsePlayerError),
+        }
+    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
 pub enum Cell {
+    #[default]
     Empty,
     Set(Player),
+    /// Blocked for the whole game, e.g. in puzzles. Pieces land on top of it
+    /// and it's never part of a line.
+    Blocked,
 }
 
-impl Default for Cell {
-    fn default() -> Self {
-        Cell::Empty
+impl Cell {
+    /// Character used by `Board::print_board` and the text format
+    pub fn symbol(self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Set(player) => player.symbol(),
+            Cell::Blocked => '#',
+        }
+    }
+
+    /// Inverse of `symbol`
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(Cell::Empty),
+            '#' => Some(Cell::Blocked),
+            c => Player::from_char(c).map(Cell::Set),
+        }
+    }
+
+    /// Symbol used by the `Display` impl of `Board`
+    pub fn glyph(self) -> &'static str {
+        match self {
+            Cell::Empty => "·",
+            Cell::Set(Player::O) => "O",
+            Cell::Set(Player::X) => "X",
+            Cell::Blocked => "#",
+        }
     }
 }
 
 #[derive(Clone, Copy, Eq, PartialEq)]
-pub struct State([[Cell; COLUMNS as usize]; ROWS as usize], Player);
+pub struct State {
+    board: Board,
+    player: Player,
+    /// Number of pieces in a row needed to win.
+    /// Wins are impossible if it exceeds the board's width and height.
+    win_length: u8,
+    /// Zobrist hash, updated with each move
+    hash: u64,
+    /// Number of pieces and blocked cells in each column, updated with each move
+    heights: [u8; COLUMNS as usize],
+}
+
+impl Hash for State {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash)
+    }
+}
 
 impl Default for State {
     fn default() -> Self {
-        Self(Default::default(), Player::O)
+        Self::empty()
     }
 }
 
-impl std::fmt::Debug for State {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Current player: {:?}", self.1)?;
+impl core::fmt::Debug for State {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Current player: {:?}", self.player)?;
         writeln!(f, "{}", self.print_board())?;
         Ok(())
     }
 }
 
+/// Board with column numbers above it, for players
+impl core::fmt::Display for State {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.board.fmt(f)
+    }
+}
+
 /// Column (left to right), then Row (top to bottom)
 impl Index<(u8, u8)> for State {
     type Output = Cell;
 
     fn index(&self, index: (u8, u8)) -> &Self::Output {
-        &self.0[index.1 as usize][index.0 as usize]
-    }
-}
-impl IndexMut<(u8, u8)> for State {
-    fn index_mut(&mut self, index: (u8, u8)) -> &mut Self::Output {
-        &mut self.0[index.1 as usize][index.0 as usize]
+        &self.board[index]
     }
 }
 
+/// Result of a move on a `State` or another board representation
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum MoveResult {
+pub enum MoveResult<S = State> {
     Impossible,
     Victory,
-    State(State),
+    State(S),
+}
+
+impl<S> core::fmt::Display for MoveResult<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MoveResult::Impossible => write!(f, "move can't be played"),
+            MoveResult::Victory => write!(f, "move wins"),
+            MoveResult::State(_) => write!(f, "game goes on"),
+        }
+    }
 }
 
+/// Why a move can't be played
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MoveError {
+    ColumnFull,
+    /// There is no column with that number
+    ColumnOutOfRange,
+    /// The game has already been won
+    GameOver,
+}
+
+impl core::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MoveError::ColumnFull => write!(f, "column is full"),
+            MoveError::ColumnOutOfRange => write!(f, "column must be below {}", COLUMNS),
+            MoveError::GameOver => write!(f, "game is already won"),
+        }
+    }
+}
+
+impl core::error::Error for MoveError {}
+
+/// Why a sequence of moves can't be played, with the index of the offending move
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SequenceError {
+    Move(usize, MoveError),
+    /// The character isn't a column number
+    InvalidCharacter(usize),
+}
+
+impl core::fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SequenceError::Move(index, error) => write!(f, "move {}: {}", index, error),
+            SequenceError::InvalidCharacter(index) => {
+                write!(f, "move {} isn't a column number", index)
+            }
+        }
+    }
+}
+
+impl core::error::Error for SequenceError {}
+
+/// Why a position can't arise in a game. Rows and columns are numbered from 0,
+/// rows from the top.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InvalidPosition {
+    /// The piece at (column, row) has an empty cell below it
+    FloatingPiece(u8, u8),
+    /// The side to move doesn't have as many pieces as the other side or one fewer
+    WrongSideToMove,
+    /// Both players have `win_length` pieces in a row
+    BothWon,
+    /// The stone with the given index of a setup goes into a full column
+    /// or one past the edge of the board
+    NoRoomForStone(usize),
+}
+
+impl core::fmt::Display for InvalidPosition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InvalidPosition::FloatingPiece(column, row) => write!(
+                f,
+                "piece in column {}, row {} has an empty cell below it",
+                column, row
+            ),
+            InvalidPosition::WrongSideToMove => write!(
+                f,
+                "side to move must have as many pieces as the other side or one fewer"
+            ),
+            InvalidPosition::BothWon => write!(f, "both players have a line"),
+            InvalidPosition::NoRoomForStone(index) => {
+                write!(f, "no room for stone {} in its column", index)
+            }
+        }
+    }
+}
+
+impl core::error::Error for InvalidPosition {}
+
 impl State {
+    /// Empty board with `starting_player` to move
+    pub fn new(starting_player: Player) -> Self {
+        Self::from_board(Default::default(), starting_player)
+    }
+
+    /// Empty board with `starting_player` to move, won with `win_length` pieces in a row
+    pub fn with_win_length(starting_player: Player, win_length: u8) -> Self {
+        Self {
+            win_length,
+            ..Self::new(starting_player)
+        }
+    }
+
+    fn from_board(board: [[Cell; COLUMNS as usize]; ROWS as usize], player: Player) -> Self {
+        let board = Board(board);
+        let mut state = Self {
+            board,
+            player,
+            win_length: WINNING_LENGTH,
+            hash: 0,
+            heights: board.heights(),
+        };
+        state.hash = state.zobrist_hash();
+        state
+    }
+
+    /// Empty board with `Player::O` to move
+    pub fn empty() -> Self {
+        Self::new(Player::O)
+    }
+
+    /// The same position with the columns in reverse order.
+    /// Both have the same result, with the moves mirrored as well.
+    pub fn mirrored(&self) -> Self {
+        Self {
+            win_length: self.win_length,
+            ..Self::from_board(self.board.mirrored().0, self.player)
+        }
+    }
+
+    /// The column in the mirrored position that corresponds to `column`
+    pub fn mirror_move(column: Column) -> Column {
+        Column(COLUMNS - 1 - column.0)
+    }
+
+    /// Plays `moves` starting from the empty board with `Player::O` to move.
+    /// Only the last move may win the game.
+    pub fn from_moves(moves: &[u8]) -> Result<Self, SequenceError> {
+        let mut state = Self::empty();
+        let mut won = false;
+        for (index, &column) in moves.iter().enumerate() {
+            if won {
+                return Err(SequenceError::Move(index, MoveError::GameOver));
+            }
+            won = state
+                .try_move_mut(column)
+                .map_err(|error| SequenceError::Move(index, error))?
+                .is_some();
+        }
+        debug_assert_eq!(state.validate(), Ok(()));
+        Ok(state)
+    }
+
+    /// Board with `stones` dropped into their columns in order and `to_move`
+    /// to move, e.g. to give the weaker side a head start. As the side to
+    /// move is given, the players can have any number of pieces, but the
+    /// position is otherwise checked as by `validate`.
+    pub fn with_setup(stones: &[(u8, Player)], to_move: Player) -> Result<Self, InvalidPosition> {
+        let mut state = Self::new(to_move);
+        for (index, &(column, player)) in stones.iter().enumerate() {
+            let row = state
+                .drop_row(column)
+                .ok_or(InvalidPosition::NoRoomForStone(index))?;
+            state.board[(column, row)] = Cell::Set(player);
+            state.heights[column as usize] += 1;
+            state.hash ^= zobrist::piece(column, row, player);
+        }
+        state.check_setup()
+    }
+
+    /// Position with the cells of `grid`, row by row from the top, and
+    /// `to_move` to move. Checked like the result of `with_setup`, e.g. that
+    /// no piece floats above an empty cell.
+    pub fn from_grid(
+        grid: [[Cell; COLUMNS as usize]; ROWS as usize],
+        to_move: Player,
+    ) -> Result<Self, InvalidPosition> {
+        Self::from_board(grid, to_move).check_setup()
+    }
+
+    /// The cells row by row from the top, each row from left to right
+    pub fn grid(&self) -> &[[Cell; COLUMNS as usize]; ROWS as usize] {
+        &self.board.0
+    }
+
+    /// This position if it passes `validate`, ignoring the number of pieces
+    /// of each side
+    fn check_setup(self) -> Result<Self, InvalidPosition> {
+        self.check(false)?;
+        Ok(self)
+    }
+
+    /// Like `from_moves`, with one digit per move, e.g. "3343215"
+    pub fn from_moves_str(moves: &str) -> Result<Self, SequenceError> {
+        let moves = moves
+            .chars()
+            .enumerate()
+            .map(|(index, c)| match c.to_digit(10) {
+                Some(column) => Ok(column as u8),
+                None => Err(SequenceError::InvalidCharacter(index)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_moves(&moves)
+    }
+
+    /// The cells, without the side to move
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Same as `Board::cells`
+    pub fn cells(&self) -> impl Iterator<Item = (u8, u8, Cell)> + '_ {
+        self.board.cells()
+    }
+
+    /// Number of pieces of `Player::O` and of `Player::X`
+    pub fn count_pieces(&self) -> (usize, usize) {
+        self.board.count_pieces()
+    }
+
+    /// Checks that the position can arise in a game: every piece lies on
+    /// another one or the bottom, the side to move has as many pieces as the
+    /// other side or one fewer, and at most one player has a line
+    pub fn validate(&self) -> Result<(), InvalidPosition> {
+        self.check(true)
+    }
+
+    /// `validate`, checking the number of pieces of each side only if
+    /// `count_pieces` is set
+    fn check(&self, count_pieces: bool) -> Result<(), InvalidPosition> {
+        if let Some((column, row)) = self.board.floating_piece() {
+            return Err(InvalidPosition::FloatingPiece(column, row));
+        }
+        let (o, x) = self.count_pieces();
+        let (own, other) = match self.player {
+            Player::O => (o, x),
+            Player::X => (x, o),
+        };
+        if count_pieces && own != other && own + 1 != other {
+            return Err(InvalidPosition::WrongSideToMove);
+        }
+        let mut lines = self.board.winning_lines(self.win_length);
+        if let Some((first, ..)) = lines.next() {
+            if lines.any(|(player, ..)| player != first) {
+                return Err(InvalidPosition::BothWon);
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as `Board::print_board`
     pub fn print_board(&self) -> String {
-        let mut out = String::new();
-        for row in &self.0 {
-            for cell in row {
-                out.push(match cell {
-                    Cell::Empty => '.',
-                    Cell::Set(Player::O) => 'o',
-                    Cell::Set(Player::X) => 'x',
-                })
+        self.board.print_board()
+    }
+
+    pub fn current_player(&self) -> Player {
+        self.player
+    }
+
+    pub fn win_length(&self) -> u8 {
+        self.win_length
+    }
+
+    /// Hash of the position, computed from scratch.
+    /// Equal to the hash that is maintained incrementally while playing.
+    pub fn zobrist_hash(&self) -> u64 {
+        let mut hash = zobrist::side_to_move(self.player);
+        for (column, row, cell) in self.cells() {
+            match cell {
+                Cell::Set(player) => hash ^= zobrist::piece(column, row, player),
+                Cell::Blocked => hash ^= zobrist::blocked(column, row),
+                Cell::Empty => (),
             }
-            out.push('\n');
         }
-        out
+        hash
+    }
+
+    /// Player with `win_length` pieces in a row anywhere on the board.
+    /// If both players have one, which can't happen in a game, returns either.
+    pub fn winner(&self) -> Option<Player> {
+        self.board.winner(self.win_length)
+    }
+
+    /// Cells (column, row) of `win_length` pieces in a row, in order along the line.
+    /// If there are several such lines, returns any one of them.
+    pub fn winning_cells(&self) -> Option<Vec<(u8, u8)>> {
+        self.board.winning_cells(self.win_length)
+    }
+
+    /// Whether no more pieces can be placed
+    pub fn is_full(&self) -> bool {
+        self.heights.iter().all(|&height| height == ROWS)
+    }
+
+    /// Whether the board is full without a winner
+    pub fn is_draw(&self) -> bool {
+        self.is_full() && self.winner().is_none()
     }
 
-    pub fn player(&self) -> Player {
-        self.1
+    /// Whether the game is over, because a player has won or the board is full
+    pub fn is_terminal(&self) -> bool {
+        self.is_full() || self.winner().is_some()
     }
 
+    /// Number of moves played, i.e. pieces on the board
     pub fn turn(&self) -> usize {
-        self.0
+        let (o, x) = self.count_pieces();
+        o + x
+    }
+
+    /// Number of cells pieces can still be placed in
+    pub fn empty_cells(&self) -> usize {
+        self.heights
             .iter()
-            .flatten()
-            .filter(|cell| matches!(cell, Cell::Set(_)))
-            .count()
+            .map(|&height| (ROWS - height) as usize)
+            .sum()
     }
 
-    pub fn try_move(&self, column: u8) -> MoveResult {
-        if let Cell::Empty = self[(column, 0)] {
-            // Let gravity do its thing
-            fn row(state: &State, column: u8) -> u8 {
-                for row in 1..ROWS {
-                    if let Cell::Set(_) = state[(column, row)] {
-                        return row - 1;
-                    }
-                }
-                ROWS - 1
-            }
-            let row = row(self, column);
-
-            // Check for horizontal line
-            let mut min_column = column;
-            for column in (0..column).rev() {
-                if self[(column, row)] == Cell::Set(self.1) {
-                    min_column = column;
-                } else {
-                    break;
-                }
-            }
-            let mut max_column = column;
-            for column in (column + 1)..COLUMNS {
-                if self[(column, row)] == Cell::Set(self.1) {
-                    max_column = column;
-                } else {
-                    break;
-                }
+    /// Columns that aren't full yet, left to right
+    pub fn legal_moves(&self) -> impl Iterator<Item = Column> + '_ {
+        (0..COLUMNS)
+            .filter(move |&column| self.heights[column as usize] < ROWS)
+            .map(Column)
+    }
+
+    /// Drops a piece into `column` for the side to move.
+    /// `Impossible` if the column is full.
+    pub fn try_move(&self, column: Column) -> MoveResult {
+        let column = column.0;
+        match self.drop_row(column) {
+            None => MoveResult::Impossible,
+            Some(row) if self.check_win_through(column, row, self.player) => MoveResult::Victory,
+            Some(row) => MoveResult::State({
+                let mut new = *self;
+                new.place(column, row);
+                new
+            }),
+        }
+    }
+
+    /// Like `try_move`, but updates this state instead of returning a new one.
+    /// Returns the winner if the move wins, in which case the piece is still placed.
+    pub fn try_move_mut(&mut self, column: u8) -> Result<Option<Player>, MoveError> {
+        self.check_column(column)?;
+        let row = self.drop_row(column).ok_or(MoveError::ColumnFull)?;
+        let player = self.player;
+        let winner = Some(player).filter(|&player| self.check_win_through(column, row, player));
+        self.place(column, row);
+        Ok(winner)
+    }
+
+    /// The state after the side to move puts a piece on the given cell,
+    /// without checking the move or whether it wins, for hot loops that
+    /// already know both.
+    ///
+    /// The cell has to be where a piece dropped into `column` lands, i.e.
+    /// `column` has to be on the board and not full and `row` its lowest empty
+    /// cell. This is only checked in debug builds. Otherwise the result is an
+    /// inconsistent state, e.g. with a floating piece or a wrong hash, though
+    /// never undefined behavior.
+    pub fn apply_unchecked(&self, column: u8, row: u8) -> State {
+        debug_assert!(
+            column < COLUMNS && self.drop_row(column) == Some(row),
+            "piece can't be placed at column {} row {}",
+            column,
+            row
+        );
+        let mut new = *self;
+        new.place(column, row);
+        new
+    }
+
+    /// Blocks the lowest empty cell of `column` for the rest of the game.
+    /// This isn't a move, the side to move stays the same.
+    pub fn block_cell(&mut self, column: u8) -> Result<(), MoveError> {
+        self.check_column(column)?;
+        let row = self.drop_row(column).ok_or(MoveError::ColumnFull)?;
+        self.board[(column, row)] = Cell::Blocked;
+        self.heights[column as usize] += 1;
+        self.hash ^= zobrist::blocked(column, row);
+        Ok(())
+    }
+
+    /// Like `try_move`, but takes any column number and tells why a move is
+    /// impossible instead of returning `MoveResult::Impossible`
+    pub fn play(&self, column: u8) -> Result<MoveResult, MoveError> {
+        match self.try_move(Column::try_from(column)?) {
+            MoveResult::Impossible => Err(MoveError::ColumnFull),
+            result => Ok(result),
+        }
+    }
+
+    fn check_column(&self, column: u8) -> Result<(), MoveError> {
+        if column < COLUMNS {
+            Ok(())
+        } else {
+            Err(MoveError::ColumnOutOfRange)
+        }
+    }
+
+    /// Row a piece dropped into `column` lands in, if the column isn't full
+    fn drop_row(&self, column: u8) -> Option<u8> {
+        // Let gravity do its thing
+        (ROWS - 1).checked_sub(*self.heights.get(column as usize)?)
+    }
+
+    /// Whether a piece of `player` at the given cell completes a line,
+    /// only looking at lines through that cell. The cell counts as theirs
+    /// whatever it contains. Much cheaper than scanning the board with
+    /// `winner` when only a few cells changed.
+    pub fn check_win_through(&self, column: u8, row: u8, player: Player) -> bool {
+        // Check for horizontal line
+        let mut min_column = column;
+        for column in (0..column).rev() {
+            if self[(column, row)] == Cell::Set(player) {
+                min_column = column;
+            } else {
+                break;
             }
-            if max_column - min_column + 1 >= WINNING_LENGTH {
-                return MoveResult::Victory;
+        }
+        let mut max_column = column;
+        for column in (column + 1)..COLUMNS {
+            if self[(column, row)] == Cell::Set(player) {
+                max_column = column;
+            } else {
+                break;
             }
+        }
+        if max_column - min_column + 1 >= self.win_length {
+            return true;
+        }
 
-            // Check for vertical line
-            let mut min_row = row;
-            for row in (0..row).rev() {
-                if self[(column, row)] == Cell::Set(self.1) {
-                    min_row = row;
-                } else {
-                    break;
-                }
-            }
-            let mut max_row = row;
-            for row in (row + 1)..ROWS {
-                if self[(column, row)] == Cell::Set(self.1) {
-                    max_row = row;
-                } else {
-                    break;
-                }
+        // Check for vertical line
+        let mut min_row = row;
+        for row in (0..row).rev() {
+            if self[(column, row)] == Cell::Set(player) {
+                min_row = row;
+            } else {
+                break;
             }
-            if max_row - min_row + 1 >= WINNING_LENGTH {
-                return MoveResult::Victory;
+        }
+        let mut max_row = row;
+        for row in (row + 1)..ROWS {
+            if self[(column, row)] == Cell::Set(player) {
+                max_row = row;
+            } else {
+                break;
             }
+        }
+        if max_row - min_row + 1 >= self.win_length {
+            return true;
+        }
 
-            // Check for bottom-left to top-right
-            let mut min = column;
-            for offset in 1..(ROWS - row).min(column + 1) {
-                if self[(column - offset, row + offset)] == Cell::Set(self.1) {
-                    min -= 1;
-                } else {
-                    break;
-                }
+        // Check for bottom-left to top-right
+        let mut min = column;
+        for offset in 1..(ROWS - row).min(column + 1) {
+            if self[(column - offset, row + offset)] == Cell::Set(player) {
+                min -= 1;
+            } else {
+                break;
             }
-            let mut max = column;
-            for offset in 1..(row + 1).min(COLUMNS - column) {
-                if self[(column + offset, row - offset)] == Cell::Set(self.1) {
-                    max += 1;
-                } else {
-                    break;
-                }
-            }
-            if max - min + 1 >= WINNING_LENGTH {
-                return MoveResult::Victory;
+        }
+        let mut max = column;
+        for offset in 1..(row + 1).min(COLUMNS - column) {
+            if self[(column + offset, row - offset)] == Cell::Set(player) {
+                max += 1;
+            } else {
+                break;
             }
+        }
+        if max - min + 1 >= self.win_length {
+            return true;
+        }
 
-            // Check for top-left to bottom-right
-            let mut min = column;
-            for offset in 1..(row.min(column) + 1) {
-                if self[(column - offset, row - offset)] == Cell::Set(self.1) {
-                    min -= 1;
-                } else {
-                    break;
-                }
-            }
-            let mut max = column;
-            for offset in 1..(ROWS - row).min(COLUMNS - column) {
-                if self[(column + offset, row + offset)] == Cell::Set(self.1) {
-                    max += 1;
-                } else {
-                    break;
-                }
+        // Check for top-left to bottom-right
+        let mut min = column;
+        for offset in 1..(row.min(column) + 1) {
+            if self[(column - offset, row - offset)] == Cell::Set(player) {
+                min -= 1;
+            } else {
+                break;
             }
-            if max - min + 1 >= WINNING_LENGTH {
-                return MoveResult::Victory;
+        }
+        let mut max = column;
+        for offset in 1..(ROWS - row).min(COLUMNS - column) {
+            if self[(column + offset, row + offset)] == Cell::Set(player) {
+                max += 1;
+            } else {
+                break;
             }
+        }
+        if max - min + 1 >= self.win_length {
+            return true;
+        }
 
-            // Not a winning move
-            MoveResult::State({
-                let mut new = State(self.0, self.1.other());
-                new[(column, row)] = Cell::Set(self.1);
-                new
-            })
+        false
+    }
+
+    /// Puts a piece of the side to move on the given cell and passes the turn
+    fn place(&mut self, column: u8, row: u8) {
+        self.board[(column, row)] = Cell::Set(self.player);
+        self.heights[column as usize] += 1;
+        self.hash ^= zobrist::piece(column, row, self.player) ^ zobrist::side_to_move(Player::X);
+        self.player = self.player.other();
+    }
+}
+
+/// Positions with some winning moves for `Player::O`
+#[cfg(test)]
+#[rustfmt::skip]
+fn winning_moves_fixtures() -> [State; 2] {
+    use Player::*;
+    use Cell::*;
+
+    [
+        State::from_grid(
+            [
+                [Empty,  Empty,  Empty,  Empty,  Empty, Set(X), Empty],
+                [Empty,  Empty,  Empty,  Empty,  Empty, Set(X), Empty],
+                [Empty,  Empty,  Empty,  Empty,  Empty, Set(O), Empty],
+                [Set(X), Set(O), Empty,  Empty,  Empty, Set(X), Empty],
+                [Set(X), Set(O), Set(O), Empty,  Empty, Set(X), Empty],
+                [Set(X), Set(O), Set(O), Set(O), Empty, Set(X), Empty],
+            ],
+            O,
+        )
+        .unwrap(),
+        State::from_grid(
+            [
+                [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+                [Empty,  Empty,  Empty,  Empty,  Set(O), Empty,  Empty],
+                [Empty,  Empty,  Empty,  Empty,  Set(O), Set(O), Empty],
+                [Empty,  Empty,  Set(X), Empty,  Set(O), Set(X), Set(X)],
+                [Empty,  Empty,  Set(O), Empty,  Set(X), Set(X), Set(O)],
+                [Set(X), Empty,  Set(O), Set(O), Set(X), Set(O), Set(X)],
+            ],
+            O,
+        )
+        .unwrap(),
+    ]
+}
+
+#[test]
+fn test_winning_moves() {
+    let [state, other_state] = winning_moves_fixtures();
+
+    assert!(matches!(state.try_move(Column(0)), MoveResult::Victory));
+    assert!(matches!(state.try_move(Column(1)), MoveResult::Victory));
+    assert!(matches!(state.try_move(Column(2)), MoveResult::State(_)));
+    assert!(matches!(state.try_move(Column(3)), MoveResult::State(_)));
+    assert!(matches!(state.try_move(Column(4)), MoveResult::Victory));
+    assert!(matches!(state.try_move(Column(5)), MoveResult::Impossible));
+    assert!(matches!(state.try_move(Column(6)), MoveResult::State(_)));
+
+    let state = other_state;
+    assert!(matches!(state.try_move(Column(0)), MoveResult::State(_)));
+    assert!(matches!(state.try_move(Column(1)), MoveResult::State(_)));
+    assert!(matches!(state.try_move(Column(2)), MoveResult::State(_)));
+    assert!(matches!(state.try_move(Column(3)), MoveResult::Victory));
+    assert!(matches!(state.try_move(Column(4)), MoveResult::Victory));
+    assert!(matches!(state.try_move(Column(5)), MoveResult::State(_)));
+    assert!(matches!(state.try_move(Column(6)), MoveResult::State(_)));
+}
+
+#[test]
+fn test_new() {
+    let state = State::new(Player::X);
+    assert_eq!(state.current_player(), Player::X);
+    assert_eq!(state.turn(), 0);
+    assert_eq!(State::empty(), State::new(Player::O));
+}
+
+/// Full board without any four-in-a-row
+#[cfg(test)]
+#[rustfmt::skip]
+fn drawn_board() -> State {
+    use Player::*;
+    use Cell::*;
+
+    State::from_board(
+        [
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+        ],
+        O,
+    )
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_symbols() {
+    for &player in &[Player::O, Player::X] {
+        assert_eq!(Player::from_char(player.symbol()), Some(player));
+        assert_eq!(Cell::from_char(player.symbol()), Some(Cell::Set(player)));
+    }
+    assert_eq!(Cell::from_char(Cell::Empty.symbol()), Some(Cell::Empty));
+    assert_eq!(Player::from_char('.'), None);
+    assert_eq!(Cell::from_char('O'), None);
+}
+
+#[test]
+fn test_cells() {
+    let state = State::from_moves(&[3, 3, 2, 4, 3, 2, 4, 4, 2]).unwrap();
+    let cells: Vec<_> = state.cells().collect();
+    assert_eq!(cells.len(), (COLUMNS * ROWS) as usize);
+    for (index, &(column, row, cell)) in cells.iter().enumerate() {
+        assert_eq!((row * COLUMNS + column) as usize, index);
+        assert_eq!(state[(column, row)], cell);
+    }
+    let symbols: String = cells.iter().map(|(.., cell)| cell.symbol()).collect();
+    assert_eq!(symbols, state.print_board().replace('\n', ""));
+}
+
+#[test]
+fn test_count_pieces() {
+    assert_eq!(State::empty().count_pieces(), (0, 0));
+    let moves = [3, 3, 2, 4, 3, 2, 4, 4, 2];
+    for length in 1..=moves.len() {
+        let (o, x) = State::from_moves(&moves[..length]).unwrap().count_pieces();
+        assert_eq!(o + x, length);
+        if length % 2 == 1 {
+            assert_eq!(o, x + 1);
         } else {
-            MoveResult::Impossible
+            assert_eq!(o, x);
         }
     }
+    assert_eq!(drawn_board().count_pieces(), (21, 21));
 }
 
-#[derive(Clone, Copy, Debug)]
-pub enum Eval {
-    ImmediateVictory,
-    AssuredVictory,
-    AssuredLoss,
-    Neutral,
+#[rustfmt::skip]
+#[test]
+fn test_validate() {
+    use Cell::*;
+    use Player::*;
+
+    for state in [State::empty(), State::new(X), drawn_board()] {
+        assert_eq!(state.validate(), Ok(()));
+    }
+    let won = State::from_moves(&[0, 1, 0, 1, 0, 1, 0]).unwrap();
+    assert_eq!(won.validate(), Ok(()));
+
+    let both_won = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+        ],
+        O,
+    );
+    assert_eq!(both_won.validate(), Err(InvalidPosition::BothWon));
+
+    // X has two pieces fewer than O
+    let too_few = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty],
+            [Empty,  Empty,  Set(X), Set(O), Set(O), Empty,  Empty],
+        ],
+        X,
+    );
+    assert_eq!(too_few.validate(), Err(InvalidPosition::WrongSideToMove));
+    // With one piece fewer, it's X's turn
+    let mut board = too_few.board.0;
+    board[4][3] = Empty;
+    assert_eq!(State::from_board(board, X).validate(), Ok(()));
+    assert_eq!(State::from_board(board, O).validate(), Err(InvalidPosition::WrongSideToMove));
+
+    board[5][2] = Empty;
+    board[4][2] = Set(X);
+    assert_eq!(State::from_board(board, X).validate(), Err(InvalidPosition::FloatingPiece(2, 4)));
 }
 
-pub type NextMove = SmallVec<[u8; COLUMNS as usize]>;
+#[test]
+fn test_with_setup() {
+    use Player::*;
+
+    // Two handicap stones for O in the center, X moves first
+    let state = State::with_setup(&[(3, O), (3, O)], X).unwrap();
+    assert_eq!(state.current_player(), X);
+    assert_eq!(state.count_pieces(), (2, 0));
+    assert_eq!(state[(3, ROWS - 1)], Cell::Set(O));
+    assert_eq!(state[(3, ROWS - 2)], Cell::Set(O));
+    assert_eq!(state.heights, [0, 0, 0, 2, 0, 0, 0]);
+    assert_eq!(state.hash, state.zobrist_hash());
+    let next = state.play(3).unwrap();
+    assert!(matches!(next, MoveResult::State(next) if next[(3, ROWS - 3)] == Cell::Set(X)));
 
-pub fn find_next_move(state: &State, depth: u8, parallelize: bool) -> (NextMove, Eval) {
-    let mut move_states: SmallVec<[(u8, State); COLUMNS as usize]> = SmallVec::new();
-    for column in 0..7 {
-        match state.try_move(column) {
-            MoveResult::Victory => return (smallvec![column], Eval::ImmediateVictory),
-            MoveResult::Impossible => (),
-            MoveResult::State(next) => move_states.push((column, next)),
-        }
+    // Stones of both players stack in the order they're given
+    let state = State::with_setup(&[(0, X), (6, O), (0, O)], O).unwrap();
+    assert_eq!(state.board, State::from_moves(&[6, 0, 0]).unwrap().board);
+    assert_eq!(state.current_player(), O);
+    assert_eq!(State::with_setup(&[], X), Ok(State::new(X)));
+
+    let full = [(2, O); ROWS as usize];
+    assert_eq!(
+        State::with_setup(&[&full[..], &[(2, X)]].concat(), X),
+        Err(InvalidPosition::NoRoomForStone(ROWS as usize))
+    );
+    assert_eq!(
+        State::with_setup(&[(3, O), (COLUMNS, O)], X),
+        Err(InvalidPosition::NoRoomForStone(1))
+    );
+    let both_won: Vec<_> = (0..4).flat_map(|_| [(0, O), (1, X)]).collect();
+    assert_eq!(
+        State::with_setup(&both_won, O),
+        Err(InvalidPosition::BothWon)
+    );
+    // Also with a handicap stone, where the numbers of pieces differ
+    let both_won = [&both_won[..], &[(6, O)]].concat();
+    assert_eq!(
+        State::with_setup(&both_won, X),
+        Err(InvalidPosition::BothWon)
+    );
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_from_grid() {
+    use Player::*;
+    use Cell::*;
+
+    let [state, _] = winning_moves_fixtures();
+    assert_eq!(State::from_grid(*state.grid(), O), Ok(state));
+    assert_eq!(Board::from(*state.grid()), *state.board());
+    assert_eq!(State::from_grid(Default::default(), X), Ok(State::new(X)));
+
+    let floating = [
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Set(X), Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty],
+    ];
+    assert_eq!(State::from_grid(floating, O), Err(InvalidPosition::FloatingPiece(3, 3)));
+    // O has two pieces more than X
+    let floating = [
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty],
+    ];
+    assert_eq!(State::from_grid(floating, X), Err(InvalidPosition::FloatingPiece(3, 3)));
+    // Both have a line, O with five pieces against four
+    let both_won = [
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+        [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+        [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+        [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Empty],
+        [Set(O), Set(X), Empty,  Empty,  Empty,  Empty,  Set(O)],
+    ];
+    assert_eq!(State::from_grid(both_won, X), Err(InvalidPosition::BothWon));
+}
+
+#[test]
+fn test_blocked_cells() {
+    // Pieces land on top of blocked cells
+    let mut state = State::empty();
+    state.block_cell(3).unwrap();
+    assert_eq!(state.current_player(), Player::O);
+    state.try_move_mut(3).unwrap();
+    assert_eq!(state[(3, 5)], Cell::Blocked);
+    assert_eq!(state[(3, 4)], Cell::Set(Player::O));
+    assert_eq!((state.turn(), state.empty_cells()), (1, 40));
+    assert_eq!(state.zobrist_hash(), state.hash);
+    assert_eq!(State::from_text(&state.to_text()), Ok(state));
+    assert_eq!(State::from(BitBoard::from(state)), state);
+
+    for _ in 0..4 {
+        state.block_cell(3).unwrap();
     }
+    assert_eq!(state.block_cell(3), Err(MoveError::ColumnFull));
+    assert_eq!(state.try_move(Column(3)), MoveResult::Impossible);
 
-    let mut moves_evals: SmallVec<[_; COLUMNS as usize]> =
-        smallvec![(255, Eval::Neutral); move_states.len()];
-    let eval = |((column, state), res): (&mut (u8, State), &mut (u8, Eval))| {
-        let eval = if depth > 0 {
-            find_next_move(state, depth - 1, false).1
-        } else {
-            Eval::Neutral
+    // The blocked cell takes the place O needs to complete the bottom row
+    let moves = [0, 6, 1, 6, 2, 6];
+    let unblocked = State::from_moves(&moves).unwrap();
+    assert_eq!(unblocked.try_move(Column(3)), MoveResult::Victory);
+    let mut state = State::empty();
+    state.block_cell(3).unwrap();
+    for &column in &moves {
+        state.try_move_mut(column).unwrap();
+    }
+    assert!(matches!(state.try_move(Column(3)), MoveResult::State(_)));
+    assert!(matches!(
+        BitBoard::from(state).try_move(Column(3)),
+        MoveResult::State(_)
+    ));
+    assert_eq!(state.immediate_threats(Player::O), []);
+    assert_eq!(state.immediate_threats(Player::X), [(6, 2)]);
+}
+
+#[test]
+fn test_parse_player() {
+    for &(s, player) in &[
+        ("o", Player::O),
+        ("O", Player::O),
+        ("1", Player::O),
+        ("x", Player::X),
+        ("X", Player::X),
+        ("2", Player::X),
+    ] {
+        assert_eq!(s.parse(), Ok(player));
+    }
+    for s in &["", "0", "3", "oo", " o", "y", "Player O"] {
+        assert_eq!(s.parse::<Player>(), Err(ParsePlayerError));
+    }
+    assert!(ParsePlayerError.to_string().contains("O or X"));
+}
+
+#[test]
+fn test_column() {
+    assert_eq!(Column::new(7), None);
+    assert_eq!(Column::new(6).map(u8::from), Some(6));
+    assert_eq!(Column::try_from(7), Err(MoveError::ColumnOutOfRange));
+    assert_eq!(Column(3).to_string(), "3");
+    assert_eq!(State::mirror_move(Column(0)), Column(6));
+}
+
+#[test]
+fn test_legal_moves() {
+    use Cell::*;
+    use Player::*;
+
+    let state = State::from_board(
+        [
+            [Set(X), Empty, Empty, Empty, Empty, Set(O), Empty],
+            [Set(O), Empty, Empty, Empty, Empty, Set(X), Empty],
+            [Set(X), Empty, Empty, Empty, Empty, Set(O), Empty],
+            [Set(O), Empty, Empty, Empty, Empty, Set(X), Empty],
+            [Set(X), Set(O), Empty, Empty, Empty, Set(O), Empty],
+            [Set(O), Set(X), Empty, Empty, Empty, Set(X), Empty],
+        ],
+        O,
+    );
+    assert_eq!(
+        state.legal_moves().map(u8::from).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 6]
+    );
+
+    assert_eq!(drawn_board().legal_moves().count(), 0);
+}
+
+#[test]
+fn test_zobrist_hash() {
+    let mut state = State::empty();
+    assert_eq!(state.hash, 0);
+    for &column in &[0, 1, 2, 3, 4, 5, 6, 6, 5, 4, 3, 2, 1, 0, 3, 3, 4] {
+        state = match state.try_move(Column(column)) {
+            MoveResult::State(next) => next,
+            _ => unreachable!(),
         };
-        *res = (*column, eval);
-    };
-    if parallelize {
-        move_states
-            .par_iter_mut()
-            .zip(moves_evals.par_iter_mut())
-            .for_each(eval);
-    } else {
-        move_states
-            .iter_mut()
-            .zip(moves_evals.iter_mut())
-            .for_each(eval);
-    }
-
-    let eval = if moves_evals
-        .iter()
-        .all(|(_, sit)| matches!(sit, Eval::ImmediateVictory | Eval::AssuredVictory))
-    {
-        Eval::AssuredLoss
-    } else if moves_evals
-        .iter()
-        .any(|(_, sit)| matches!(sit, Eval::AssuredLoss))
-    {
-        moves_evals.retain(|(_, sit)| matches!(sit, Eval::AssuredLoss));
-        Eval::AssuredVictory
-    } else {
-        // Todo: rate, prefer moves where opponent takes longer to win
-        moves_evals
-            .retain(|(_, sit)| !matches!(sit, Eval::ImmediateVictory | Eval::AssuredVictory));
-        Eval::Neutral
-    };
-    let next_moves = moves_evals.iter().map(|(column, ..)| *column).collect();
-    (next_moves, eval)
+        assert_eq!(state.hash, state.zobrist_hash());
+    }
+    assert_eq!(State::new(Player::X).hash, zobrist::SIDE_TO_MOVE);
+}
+
+#[test]
+fn test_win_length() {
+    fn play(mut state: State, columns: &[u8]) -> MoveResult {
+        let (last, columns) = columns.split_last().unwrap();
+        for &column in columns {
+            state = match state.try_move(Column(column)) {
+                MoveResult::State(next) => next,
+                result => panic!("column {} resulted in {:?}", column, result),
+            };
+        }
+        state.try_move(Column(*last))
+    }
+
+    // The board size is fixed, so all lengths are played on the 7x6 board
+    let three = State::with_win_length(Player::O, 3);
+    assert_eq!(three.win_length(), 3);
+    assert_eq!(play(three, &[0, 0, 1, 1, 2]), MoveResult::Victory);
+    assert!(matches!(
+        play(State::empty(), &[0, 0, 1, 1, 2]),
+        MoveResult::State(_)
+    ));
+
+    let five = State::with_win_length(Player::O, 5);
+    assert!(matches!(
+        play(five, &[0, 0, 1, 1, 2, 2, 3, 3]),
+        MoveResult::State(_)
+    ));
+    assert_eq!(
+        play(five, &[0, 0, 1, 1, 2, 2, 3, 3, 4]),
+        MoveResult::Victory
+    );
+
+    // Longer than the board is wide or high
+    let eight = State::with_win_length(Player::O, 8);
+    let columns = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 6, 6, 6, 6];
+    for end in 1..=columns.len() {
+        match play(eight, &columns[..end]) {
+            MoveResult::State(state) => {
+                state.heuristic_score(&EvalWeights::default());
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
 }
 
 #[rustfmt::skip]
 #[test]
-fn test_winning_moves() {
+fn test_winner() {
     use Player::*;
     use Cell::*;
 
-    let state = State(
+    let diagonal = State::from_board(
         [
-            [Empty,  Empty,  Empty,  Empty,  Empty, Set(X), Empty],
-            [Empty,  Empty,  Empty,  Empty,  Empty, Set(X), Empty],
-            [Empty,  Empty,  Empty,  Empty,  Empty, Set(O), Empty],
-            [Set(X), Set(O), Empty,  Empty,  Empty, Set(X), Empty],
-            [Set(X), Set(O), Set(O), Empty,  Empty, Set(X), Empty],
-            [Set(X), Set(O), Set(O), Set(O), Empty, Set(X), Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Set(O), Empty],
+            [Empty,  Empty,  Empty,  Empty,  Set(O), Set(X), Empty],
+            [Empty,  Empty,  Empty,  Set(O), Set(X), Set(X), Empty],
+            [Empty,  Empty,  Set(O), Set(X), Set(O), Set(X), Empty],
         ],
-        Player::O
+        X,
     );
+    assert_eq!(diagonal.winner(), Some(O));
 
-    assert!(matches!(state.try_move(0), MoveResult::Victory));
-    assert!(matches!(state.try_move(1), MoveResult::Victory));
-    assert!(matches!(state.try_move(2), MoveResult::State(_)));
-    assert!(matches!(state.try_move(3), MoveResult::State(_)));
-    assert!(matches!(state.try_move(4), MoveResult::Victory));
-    assert!(matches!(state.try_move(5), MoveResult::Impossible));
-    assert!(matches!(state.try_move(6), MoveResult::State(_)));
+    let horizontal = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Set(O), Set(O), Set(O), Empty,  Empty],
+            [Empty,  Set(O), Set(X), Set(X), Set(X), Set(X), Empty],
+        ],
+        O,
+    );
+    assert_eq!(horizontal.winner(), Some(X));
 
-    let state = State(
+    let none = State::from_board(
         [
             [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
             [Empty,  Empty,  Empty,  Empty,  Set(O), Empty,  Empty],
@@ -308,13 +1238,438 @@ fn test_winning_moves() {
             [Empty,  Empty,  Set(O), Empty,  Set(X), Set(X), Set(O)],
             [Set(X), Empty,  Set(O), Set(O), Set(X), Set(O), Set(X)],
         ],
-        Player::O
+        O,
     );
-    assert!(matches!(state.try_move(0), MoveResult::State(_)));
-    assert!(matches!(state.try_move(1), MoveResult::State(_)));
-    assert!(matches!(state.try_move(2), MoveResult::State(_)));
-    assert!(matches!(state.try_move(3), MoveResult::Victory));
-    assert!(matches!(state.try_move(4), MoveResult::Victory));
-    assert!(matches!(state.try_move(5), MoveResult::State(_)));
-    assert!(matches!(state.try_move(6), MoveResult::State(_)));
+    assert_eq!(none.winner(), None);
+    assert_eq!(drawn_board().winner(), None);
+    assert_eq!(State::empty().winner(), None);
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_is_draw() {
+    use Player::*;
+    use Cell::*;
+
+    assert!(drawn_board().is_full());
+    assert!(drawn_board().is_draw());
+
+    let won = State::from_board(
+        [
+            [Set(X), Set(X), Set(X), Set(X), Set(O), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+        ],
+        O,
+    );
+    assert!(won.is_full());
+    assert!(!won.is_draw());
+
+    let open = State::from_board(
+        [
+            [Set(X), Empty,  Empty,  Set(X), Set(X), Set(O), Empty ],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+        ],
+        X,
+    );
+    assert!(!open.is_full());
+    assert!(!open.is_draw());
+    assert!(!State::empty().is_full());
+}
+
+#[test]
+fn test_try_move_mut() {
+    let columns = [3, 3, 2, 4, 3, 2, 4, 4, 2, 6, 6, 5, 0, 0, 0, 0, 0, 0];
+    let mut chained = State::empty();
+    let mut in_place = State::empty();
+    for &column in &columns {
+        chained = match chained.try_move(Column(column)) {
+            MoveResult::State(next) => next,
+            result => panic!("column {} resulted in {:?}", column, result),
+        };
+        assert_eq!(in_place.try_move_mut(column), Ok(None));
+        assert_eq!(in_place, chained);
+    }
+    assert_eq!(in_place.try_move_mut(0), Err(MoveError::ColumnFull));
+    assert_eq!(in_place.try_move_mut(7), Err(MoveError::ColumnOutOfRange));
+    assert_eq!(in_place, chained);
+
+    // Winning move
+    assert_eq!(chained.try_move(Column(1)), MoveResult::Victory);
+    assert_eq!(in_place.try_move_mut(1), Ok(Some(Player::O)));
+    assert_eq!(in_place.winner(), Some(Player::O));
+    assert_eq!(in_place.current_player(), Player::X);
+}
+
+#[test]
+fn test_from_moves() {
+    let state = State::from_moves(&[3, 3, 4, 2, 1, 5]).unwrap();
+    assert_eq!(state.turn(), 6);
+    assert_eq!(state.current_player(), Player::O);
+    assert_eq!(state[(3, 4)], Cell::Set(Player::X));
+    assert_eq!(state[(1, 5)], Cell::Set(Player::O));
+    assert_eq!(State::from_moves_str("334215"), Ok(state));
+    assert_eq!(State::from_moves(&[]), Ok(State::empty()));
+
+    assert_eq!(
+        State::from_moves(&[0, 0, 0, 0, 0, 0, 0]),
+        Err(SequenceError::Move(6, MoveError::ColumnFull))
+    );
+    assert_eq!(
+        State::from_moves(&[3, 7]),
+        Err(SequenceError::Move(1, MoveError::ColumnOutOfRange))
+    );
+    assert_eq!(
+        State::from_moves_str("39"),
+        Err(SequenceError::Move(1, MoveError::ColumnOutOfRange))
+    );
+
+    // Winning is only allowed with the last move
+    let won = State::from_moves(&[0, 1, 0, 1, 0, 1, 0]).unwrap();
+    assert_eq!(won.winner(), Some(Player::O));
+    assert_eq!(
+        State::from_moves(&[0, 1, 0, 1, 0, 1, 0, 1]),
+        Err(SequenceError::Move(7, MoveError::GameOver))
+    );
+
+    assert_eq!(
+        State::from_moves_str("33a"),
+        Err(SequenceError::InvalidCharacter(2))
+    );
+}
+
+#[test]
+fn test_play() {
+    let state = State::from_moves(&[0, 0, 0, 0, 0, 0]).unwrap();
+    assert_eq!(state.play(7), Err(MoveError::ColumnOutOfRange));
+    assert_eq!(state.play(u8::MAX), Err(MoveError::ColumnOutOfRange));
+    assert_eq!(state.play(0), Err(MoveError::ColumnFull));
+    assert_eq!(state.play(1), Ok(state.try_move(Column(1))));
+
+    let state = State::from_moves(&[0, 1, 0, 1, 0, 1]).unwrap();
+    assert_eq!(state.play(0), Ok(MoveResult::Victory));
+}
+
+#[test]
+fn test_display() {
+    let state = State::from_moves(&[3, 3, 0]).unwrap();
+    let text = state.to_string();
+    assert_eq!(text.lines().count(), 1 + ROWS as usize);
+    assert_eq!(text.lines().next(), Some("0 1 2 3 4 5 6"));
+    assert_eq!(text.lines().last(), Some("O · · O · · ·"));
+    assert_eq!(text.lines().nth(ROWS as usize - 1), Some("· · · X · · ·"));
+}
+
+#[test]
+fn test_winning_cells() {
+    for (moves, winner) in [
+        (&[0, 0, 1, 1, 2, 2, 3][..], Player::O),
+        (&[6, 0, 1, 0, 1, 0, 1, 0], Player::X),
+        (&[0, 1, 1, 2, 2, 3, 2, 3, 3, 5, 3], Player::O),
+        (&[6, 5, 5, 4, 4, 3, 4, 3, 3, 1, 3], Player::O),
+    ] {
+        let state = State::from_moves(moves).unwrap();
+        let cells = state.winning_cells().unwrap();
+        assert_eq!(cells.len(), WINNING_LENGTH as usize);
+        assert!(cells.iter().all(|&cell| state[cell] == Cell::Set(winner)));
+        // Neighboring cells, all in the same direction
+        let steps: Vec<_> = cells
+            .windows(2)
+            .map(|pair| {
+                (
+                    pair[1].0 as i16 - pair[0].0 as i16,
+                    pair[1].1 as i16 - pair[0].1 as i16,
+                )
+            })
+            .collect();
+        assert!(steps.iter().all(|&step| step == steps[0]));
+        assert!(steps[0].0.abs() <= 1 && steps[0].1.abs() <= 1 && steps[0] != (0, 0));
+    }
+    assert_eq!(
+        State::from_moves(&[0, 0, 1, 1, 2, 2])
+            .unwrap()
+            .winning_cells(),
+        None
+    );
+}
+
+#[test]
+fn test_cached_heights() {
+    fn assert_heights_match(state: &State) {
+        for column in 0..COLUMNS {
+            let height = (0..ROWS)
+                .filter(|&row| state[(column, row)] != Cell::Empty)
+                .count();
+            assert_eq!(state.heights[column as usize] as usize, height);
+        }
+        let pieces = state
+            .board
+            .0
+            .iter()
+            .flatten()
+            .filter(|cell| **cell != Cell::Empty);
+        assert_eq!(state.turn(), pieces.count());
+    }
+
+    for seed in 0..100 {
+        let mut rng = oorandom::Rand32::new(seed);
+        let mut state = State::empty();
+        loop {
+            assert_heights_match(&state);
+            let moves: Vec<_> = state.legal_moves().collect();
+            if moves.is_empty() {
+                break;
+            }
+            let column = moves[rng.rand_range(0..moves.len() as u32) as usize];
+            if let MoveResult::State(next) = state.try_move(column) {
+                assert_heights_match(&next);
+            }
+            if state.try_move_mut(column.0).unwrap().is_some() {
+                assert_heights_match(&state);
+                break;
+            }
+        }
+    }
+    assert_heights_match(&drawn_board());
+    for state in &winning_moves_fixtures() {
+        assert_heights_match(state);
+    }
+}
+
+#[test]
+fn test_victory_matches_winner() {
+    // Directions (column step, row step) of the winning lines that were checked
+    let mut directions = Vec::new();
+    for seed in 0..1000 {
+        let mut rng = oorandom::Rand32::new(seed);
+        let mut state = State::empty();
+        loop {
+            for column in state.legal_moves() {
+                let mut placed = state;
+                placed.place(column.0, state.drop_row(column.0).unwrap());
+                match state.try_move(column) {
+                    MoveResult::Victory => {
+                        assert_eq!(placed.winner(), Some(state.player));
+                        let cells = placed.winning_cells().unwrap();
+                        let step = (
+                            cells[1].0 as i16 - cells[0].0 as i16,
+                            cells[1].1 as i16 - cells[0].1 as i16,
+                        );
+                        if !directions.contains(&step) {
+                            directions.push(step);
+                        }
+                    }
+                    MoveResult::State(next) => {
+                        assert_eq!(next, placed);
+                        assert_eq!(next.winner(), None);
+                    }
+                    MoveResult::Impossible => unreachable!(),
+                }
+            }
+            let moves: Vec<_> = state.legal_moves().collect();
+            if moves.is_empty() {
+                break;
+            }
+            let column = moves[rng.rand_range(0..moves.len() as u32) as usize];
+            match state.try_move(column) {
+                MoveResult::State(next) => state = next,
+                _ => break,
+            }
+        }
+    }
+    assert_eq!(directions.len(), 4);
+}
+
+#[test]
+fn test_wins_at_edges() {
+    /// Whether O dropping a piece on `decisive` wins with the pieces of O on
+    /// `cells`, with X filling everything below them. Checks that the rule
+    /// implementations agree.
+    fn wins(cells: &[(i16, i16)], decisive: (i16, i16)) -> bool {
+        let mut board = [[Cell::Empty; COLUMNS as usize]; ROWS as usize];
+        for &(column, row) in cells {
+            board[row as usize][column as usize] = Cell::Set(Player::O);
+        }
+        for &(column, row) in cells.iter().chain(Some(&decisive)) {
+            for row in row + 1..ROWS as i16 {
+                if board[row as usize][column as usize] == Cell::Empty {
+                    board[row as usize][column as usize] = Cell::Set(Player::X);
+                }
+            }
+        }
+        let state = State::from_board(board, Player::O);
+        let (column, row) = (decisive.0 as u8, decisive.1 as u8);
+        assert_eq!(state.drop_row(column), Some(row));
+        let bitboard = BitBoard::from(state);
+
+        let wins = state.check_win_through(column, row, Player::O);
+        assert_eq!(
+            state.try_move(Column(column)) == MoveResult::Victory,
+            wins,
+            "{:?}",
+            state
+        );
+        assert_eq!(
+            bitboard.completes_line(Player::O, BitBoard::bit(column, ROWS - 1 - row)),
+            wins,
+            "{:?}",
+            state
+        );
+        assert_eq!(
+            bitboard.try_move(Column(column)) == MoveResult::Victory,
+            wins,
+            "{:?}",
+            state
+        );
+        wins
+    }
+
+    let on_board = |&(column, row): &(i16, i16)| {
+        (0..COLUMNS as i16).contains(&column) && (0..ROWS as i16).contains(&row)
+    };
+    let mut lines = 0;
+    for &(column_step, row_step) in &[(1, 0), (0, 1), (1, 1), (1, -1)] {
+        // Starting one cell off the board, so lines sticking out are covered too
+        for column in -1..=COLUMNS as i16 {
+            for row in -1..=ROWS as i16 {
+                let line: Vec<_> = (0..WINNING_LENGTH as i16)
+                    .map(|offset| (column + offset * column_step, row + offset * row_step))
+                    .collect();
+                let inside: Vec<_> = line.iter().copied().filter(on_board).collect();
+                if inside.len() == line.len() {
+                    lines += 1;
+                }
+                for &decisive in &inside {
+                    // Only the top piece of a vertical line can be the last one
+                    if inside
+                        .iter()
+                        .any(|&(column, row)| column == decisive.0 && row < decisive.1)
+                    {
+                        continue;
+                    }
+                    let others: Vec<_> = inside
+                        .iter()
+                        .copied()
+                        .filter(|&cell| cell != decisive)
+                        .collect();
+                    assert_eq!(wins(&others, decisive), inside.len() == line.len());
+                    // One piece short
+                    for &missing in &others {
+                        let fewer: Vec<_> = others
+                            .iter()
+                            .copied()
+                            .filter(|&cell| cell != missing)
+                            .collect();
+                        assert!(!wins(&fewer, decisive));
+                    }
+                }
+            }
+        }
+    }
+    // Every line on the board, including those in the corners and along the edges
+    assert_eq!(lines, 69);
+
+    // Lines continuing on the other side of the board, as they would in a
+    // flat array of rows or of columns
+    let last_column = COLUMNS as i16 - 1;
+    let last_row = ROWS as i16 - 1;
+    for (cells, decisive) in [
+        (vec![(last_column - 1, 2), (last_column, 2), (0, 3)], (1, 3)),
+        (vec![(last_column - 1, 3), (last_column, 3), (0, 2)], (1, 2)),
+        (vec![(0, 0), (0, 1), (1, last_row)], (1, last_row - 1)),
+        (vec![(1, 0), (0, 1), (last_column, 1)], (last_column - 1, 2)),
+        (
+            vec![(0, 0), (2, last_row), (3, last_row - 1)],
+            (4, last_row - 2),
+        ),
+    ] {
+        assert!(!wins(&cells, decisive));
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    /// Random columns, including ones that don't exist, checked against a
+    /// scan of the whole board after each move
+    #[test]
+    fn test_try_move_arbitrary_columns(
+        win_length in 2..=7u8,
+        columns in proptest::collection::vec(0..=COLUMNS + 1, 0..80),
+    ) {
+        let mut state = State::with_win_length(Player::O, win_length);
+        for column in columns {
+            let result = match Column::new(column) {
+                Some(checked) => state.try_move(checked),
+                None => MoveResult::Impossible,
+            };
+            match result {
+                MoveResult::Impossible => {
+                    assert!(column >= COLUMNS || state.heights[column as usize] == ROWS);
+                    let mut unchanged = state;
+                    assert!(unchanged.try_move_mut(column).is_err());
+                }
+                MoveResult::Victory => {
+                    let mut won = state;
+                    won.place(column, state.drop_row(column).unwrap());
+                    assert_eq!(won.winner(), Some(state.player));
+                    break;
+                }
+                MoveResult::State(next) => {
+                    assert_eq!(next.winner(), None);
+                    assert_eq!(next.turn(), state.turn() + 1);
+                    assert_eq!(next.current_player(), state.player.other());
+                    state = next;
+                }
+            }
+        }
+    }
+
+    /// Columns from untrusted input are either in range or rejected
+    #[test]
+    fn test_column_from_any_number(column in proptest::num::u8::ANY) {
+        match Column::new(column) {
+            Some(checked) => {
+                assert!(column < COLUMNS);
+                assert_eq!(u8::from(checked), column);
+                assert_eq!(Column::try_from(column), Ok(checked));
+            }
+            None => assert_eq!(Column::try_from(column), Err(MoveError::ColumnOutOfRange)),
+        }
+    }
+}
+
+#[test]
+fn test_apply_unchecked() {
+    let mut states = vec![State::empty(), drawn_board()];
+    states.extend((0..20).map(|moves| {
+        State::from_moves(&[3, 3, 2, 4, 3, 2, 4, 4, 2, 6, 6, 5, 0, 0, 0, 0, 0, 0, 1, 1][..moves])
+            .unwrap()
+    }));
+    for state in states {
+        for column in state.legal_moves() {
+            let row = ROWS - 1 - state.heights[column.0 as usize];
+            let applied = state.apply_unchecked(column.0, row);
+            if let MoveResult::State(moved) = state.try_move(column) {
+                assert_eq!(applied, moved);
+            }
+            let mut moved = state;
+            moved.try_move_mut(column.0).unwrap();
+            assert_eq!(applied, moved);
+            assert_eq!(applied.hash, applied.zobrist_hash());
+        }
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic]
+fn test_apply_unchecked_floating() {
+    State::empty().apply_unchecked(3, 0);
 }
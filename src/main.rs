@@ -1,32 +1,75 @@
-mod lib;
-
 use std::{
-    io::{stdin, BufRead},
-    time::Instant,
-    todo,
+    io::{stdin, stdout, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
 };
-use structopt::{clap, StructOpt};
+use structopt::StructOpt;
 
-use lib::*;
+use connect4::{
+    analyze_game_from, find_next_move_with_book, parse_column, rank_moves, trace_search, Clock,
+    Eval, MoveError, OpeningBook, ParseError, Player, SearchStats, Selector, State, SystemClock,
+    COLUMNS, ROWS,
+};
 
 /// Play Connect 4 against the computer or let the AI fight it out.
-#[derive(StructOpt, Clone, Copy)]
+#[derive(StructOpt, Clone)]
 #[structopt(name = "connect4")]
 struct Opts {
     /// Game mode: PvP, PvC, CvC
-    mode: GameMode,
-    /// AI search tree depth.
-    /// Computation time rises exponentially width depth.
-    #[structopt(default_value = "8")]
+    #[structopt(required_unless_one(&["protocol", "analyze", "analyze-game"]))]
+    mode: Option<GameMode>,
+    /// AI search tree depth, at least 1. Depths beyond a full board are
+    /// capped. Computation time rises exponentially width depth.
+    #[structopt(long, default_value = "8", parse(try_from_str = parse_depth))]
     depth: u8,
-    /// Optional seed for the AI
+    /// Search positions with more plies left than this on multiple threads
+    #[structopt(long, default_value = "7")]
+    parallel_until_depth: u8,
+    /// Number of pieces in a row needed to win, at most the number of
+    /// columns or rows
+    #[structopt(long, alias = "win-length", default_value = "4")]
+    connect: u8,
+    /// Number of columns, at most 7. Columns further right are blocked.
+    #[structopt(long, default_value = "7", parse(try_from_str = parse_columns))]
+    cols: u8,
+    /// Number of rows, at most 6. Cells further down are blocked.
+    #[structopt(long, default_value = "6", parse(try_from_str = parse_rows))]
+    rows: u8,
+    /// Side played by the human in PvC mode: O (moves first) or X
+    #[structopt(long, default_value = "O")]
+    human: Player,
+    /// Opening book file with lines of the form "moves -> reply"
+    #[structopt(long, parse(from_os_str))]
+    book: Option<PathBuf>,
+    /// Seed for the AI, random if not given
+    #[structopt(long)]
     seed: Option<u64>,
+    /// How much the AI varies its moves: at 0 it always plays the most central
+    /// of the best moves, at e.g. 10 it sometimes plays slightly worse ones
+    #[structopt(long, default_value = "0")]
+    temperature: f32,
     /// Tell the AI to wait for the player to press enter
     #[structopt(long)]
     no_auto: bool,
     /// Print total game time
     #[structopt(long, conflicts_with("no-auto"))]
-    time: bool, // TODO: Playing field size & winning_lenght
+    time: bool,
+    /// Instead of playing, answer commands from other programs on stdin,
+    /// e.g. "position moves 3 3 4" and "go depth 8"
+    #[structopt(long)]
+    protocol: bool,
+    /// Instead of playing, print the eval of every move in the position
+    /// after these moves, e.g. "3342"
+    #[structopt(long)]
+    analyze: Option<String>,
+    /// Instead of playing, compare every move of the game with these moves,
+    /// e.g. "3342", to the best one and point out blunders
+    #[structopt(long)]
+    analyze_game: Option<String>,
+    /// Play the PvP or PvC game with clients connecting to this address,
+    /// e.g. "127.0.0.1:4000"
+    #[structopt(long)]
+    serve: Option<String>,
 }
 
 use structopt::clap::arg_enum;
@@ -41,12 +84,120 @@ arg_enum! {
 
 fn main() {
     let mut options = Opts::from_args();
-    options.seed.get_or_insert_with(random_seed);
-    match options.mode {
-        GameMode::CvC => ai_vs_ai(options),
-        GameMode::PvC => todo!(),
-        GameMode::PvP => todo!(),
+    let seed = *options.seed.get_or_insert_with(random_seed);
+    if options.connect == 0 || options.connect > options.cols.max(options.rows) {
+        eprintln!(
+            "--connect must be between 1 and the number of columns or rows, whichever is larger"
+        );
+        std::process::exit(1);
+    }
+    if let Some(moves) = &options.analyze {
+        return analyze(&options, moves);
+    }
+    if let Some(moves) = &options.analyze_game {
+        return review(&options, moves);
+    }
+    if !options.protocol {
+        println!("Seed: {}", seed);
+        println!("Depth: {}", options.depth);
+        println!(
+            "Board: {} columns, {} rows, connect {}",
+            options.cols, options.rows, options.connect
+        );
     }
+    let book = match &options.book {
+        Some(path) => match OpeningBook::load(path) {
+            Ok(book) => book,
+            Err(error) => {
+                eprintln!("Can't load opening book {}: {}", path.display(), error);
+                std::process::exit(1);
+            }
+        },
+        None => OpeningBook::new(),
+    };
+    if options.protocol {
+        return engine_protocol(options, &book);
+    }
+    if let Some(address) = options.serve.clone() {
+        return serve(options, &book, &address);
+    }
+    match options.mode.unwrap() {
+        GameMode::CvC => ai_vs_ai(options, &book),
+        GameMode::PvC => human_vs_ai(options, &book),
+        GameMode::PvP => human_vs_human(options),
+    }
+}
+
+/// Parses the `--depth` option. Searching past a full board can't change
+/// the result, so larger depths are capped.
+fn parse_depth(depth: &str) -> Result<u8, String> {
+    let depth: u32 = depth
+        .parse()
+        .map_err(|_| format!("{:?} isn't a number", depth))?;
+    if depth == 0 {
+        return Err("the AI has to search at least 1 ply".into());
+    }
+    Ok(depth.min((COLUMNS * ROWS) as u32) as u8)
+}
+
+/// Parses the `--cols` option
+fn parse_columns(columns: &str) -> Result<u8, String> {
+    parse_dimension(columns, COLUMNS)
+}
+
+/// Parses the `--rows` option
+fn parse_rows(rows: &str) -> Result<u8, String> {
+    parse_dimension(rows, ROWS)
+}
+
+fn parse_dimension(value: &str, max: u8) -> Result<u8, String> {
+    match value.parse() {
+        Ok(value) if (1..=max).contains(&value) => Ok(value),
+        _ => Err(format!("{:?} isn't a number from 1 to {}", value, max)),
+    }
+}
+
+/// Empty board with the size and winning length of `config`. Smaller boards
+/// lie in the top left corner of the full one, with the cells outside them
+/// blocked: the columns further right and the bottom of the others.
+fn new_game(config: &Opts) -> State {
+    let mut state = State::with_win_length(Player::O, config.connect);
+    for column in 0..COLUMNS {
+        let blocked = if column < config.cols {
+            ROWS - config.rows
+        } else {
+            ROWS
+        };
+        for _ in 0..blocked {
+            state.block_cell(column).unwrap();
+        }
+    }
+    state
+}
+
+/// Column entered for a board with `columns` columns, as by `parse_column`
+fn parse_board_column(s: &str, columns: u8) -> Result<u8, ParseError> {
+    match parse_column(s)? {
+        column if column < columns => Ok(column),
+        column => Err(ParseError::ColumnOutOfRange(column)),
+    }
+}
+
+/// Message for an error of `parse_board_column`
+fn column_error(error: &ParseError, columns: u8) -> String {
+    match error {
+        ParseError::ColumnOutOfRange(_) => format!("column must be below {}", columns),
+        _ => format!(
+            "columns are 0 to {} or a to {}",
+            columns - 1,
+            column_letter(columns - 1)
+        ),
+    }
+}
+
+/// Letter of `column` in the input of `parse_column`
+fn column_letter(column: u8) -> char {
+    (b'a' + column) as char
 }
 
 fn random_seed() -> u64 {
@@ -55,47 +206,487 @@ fn random_seed() -> u64 {
     u64::from_be_bytes(buf)
 }
 
-fn ai_vs_ai(config: Opts) {
-    let mut rng = oorandom::Rand32::new(config.seed.unwrap());
-    let mut pick = |possible: NextMove| {
-        if possible.len() > 0 {
-            Some(possible[rng.rand_u32() as usize % possible.len()])
-        } else {
-            None
-        }
-    };
+/// Move of the engine: the book reply if there is one, otherwise picked from
+/// the searched moves by `selector`. Book replies have no eval, and only the
+/// search at temperature 0 reports its stats.
+fn engine_move(
+    state: &State,
+    book: &OpeningBook,
+    config: &Opts,
+    selector: &mut Selector,
+) -> (u8, Option<Eval>, Option<SearchStats>) {
+    if selector.temperature() == 0. {
+        let (next_move, eval, stats) =
+            find_next_move_with_book(state, book, config.depth, config.parallel_until_depth);
+        return (next_move[0].into(), eval, eval.map(|_| stats));
+    }
+    if let Some(reply) = book.get(state) {
+        return (reply.into(), None, None);
+    }
+    // Sampling needs the evals of all moves, not just of the best ones
+    let ranking = rank_moves(state, config.depth);
+    let column = selector.select(&ranking);
+    let eval = ranking.iter().find(|&&(m, _)| m == column).unwrap().1;
+    (column.into(), Some(eval), None)
+}
+
+fn ai_vs_ai(config: Opts, book: &OpeningBook) {
+    let mut selector = Selector::new(config.temperature, config.seed.unwrap());
 
-    let time_start = Instant::now();
+    let clock = SystemClock::new();
 
-    let mut state = Default::default();
+    let mut state = new_game(&config);
     loop {
-        let (next_move, _) = find_next_move(&state, config.depth, true);
+        if state.is_draw() {
+            println!("Draw!");
+            break;
+        }
+
+        let (column, eval, stats) = engine_move(&state, book, &config, &mut selector);
+        if let Some(stats) = stats {
+            println!(
+                "Searched {} nodes ({} leaves, {} table hits, depth {}) in {:.3}s",
+                stats.nodes,
+                stats.leaf_nodes,
+                stats.tt_hits,
+                stats.max_depth_reached,
+                stats.elapsed.as_secs_f32(),
+            );
+        } else if eval.is_none() {
+            println!("Book move");
+        }
 
         if config.no_auto {
             let _ = stdin().lock().read_line(&mut String::new());
         }
 
-        if let Some(column) = pick(next_move) {
-            println!("Player {:?} plays column {}", state.player(), column,);
-            match state.try_move(column) {
-                MoveResult::State(next) => {
-                    state = next;
-                    println!("{}", state.print_board())
-                }
-                MoveResult::Victory => {
-                    println!("Victory!");
-                    break;
-                }
-                MoveResult::Impossible => unreachable!(),
+        println!(
+            "Player {:?} plays column {}",
+            state.current_player(),
+            column,
+        );
+        match state.try_move_mut(column) {
+            Ok(None) => println!("{}", render(&state, column)),
+            Ok(Some(_)) => {
+                println!("{}", render_win(&state));
+                println!("Victory!");
+                break;
+            }
+            Err(_) => unreachable!(),
+        }
+    }
+
+    if config.time {
+        println!("Time: {}", clock.now().as_secs_f32());
+    }
+}
+
+fn human_vs_ai(config: Opts, book: &OpeningBook) {
+    let mut selector = Selector::new(config.temperature, config.seed.unwrap());
+    let human = config.human;
+
+    let mut state = new_game(&config);
+    println!("{}", state);
+    loop {
+        if state.is_draw() {
+            println!("Draw!");
+            break;
+        }
+
+        let column = if state.current_player() == human {
+            match read_column(&state, config.cols) {
+                Some(column) => column,
+                None => break,
             }
         } else {
+            let (column, eval, _) = engine_move(&state, book, &config, &mut selector);
+            match eval {
+                Some(eval) => println!("Engine plays column {} ({})", column, eval),
+                None => println!("Engine plays column {} (book)", column),
+            }
+            column
+        };
+
+        match state.try_move_mut(column) {
+            Ok(None) => println!("{}", render(&state, column)),
+            Ok(Some(winner)) => {
+                println!("{}", render_win(&state));
+                if winner == human {
+                    println!("You win!");
+                } else {
+                    println!("The engine wins!");
+                }
+                break;
+            }
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+fn human_vs_human(config: Opts) {
+    let mut state = new_game(&config);
+    println!("{}", state);
+    loop {
+        if state.is_draw() {
             println!("Draw!");
             break;
         }
+
+        let column = match read_column(&state, config.cols) {
+            Some(column) => column,
+            None => break,
+        };
+        match state.try_move_mut(column) {
+            Ok(None) => println!("{}", render(&state, column)),
+            Ok(Some(winner)) => {
+                println!("{}", render_win(&state));
+                println!("Player {:?} wins!", winner);
+                break;
+            }
+            Err(_) => unreachable!(),
+        }
     }
+}
 
-    let time_end = Instant::now();
-    if config.time {
-        println!("Time: {}", (time_end - time_start).as_secs_f32());
+/// Connection of a player in `serve` mode
+struct Client {
+    player: Player,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+/// Plays a game with clients connecting over TCP: two of them in PvP mode,
+/// in PvC mode one playing the `--human` side against the engine.
+/// Messages are lines of text. Clients send the column of their move when
+/// they receive `your move`. After every move, all clients receive
+/// `played <column>` and the board as printed by `State::print_board`.
+/// The game ends with `winner <player>` or `draw`, or with `error <reason>`
+/// on an illegal move or a disconnect.
+fn serve(config: Opts, book: &OpeningBook, address: &str) {
+    let players = match config.mode {
+        Some(GameMode::PvP) => vec![Player::O, Player::X],
+        Some(GameMode::PvC) => vec![config.human],
+        _ => {
+            eprintln!("Only PvP and PvC games can be served");
+            std::process::exit(1);
+        }
+    };
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Can't listen on {}: {}", address, error);
+            std::process::exit(1);
+        }
+    };
+    println!("Listening on {}", listener.local_addr().unwrap());
+
+    let mut clients = Vec::new();
+    for player in players {
+        let (stream, peer) = listener.accept().unwrap();
+        println!("Player {:?} connected from {}", player, peer);
+        let mut client = Client {
+            player,
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        };
+        // A failed write shows up as a disconnect once the client is asked to move
+        let _ = writeln!(client.writer, "you play {:?}", player);
+        clients.push(client);
+    }
+
+    match play_session(&config, book, &mut clients) {
+        Ok(result) => println!("{}", result),
+        Err(error) => {
+            println!("Error: {}", error);
+            for client in &mut clients {
+                let _ = writeln!(client.writer, "error {}", error);
+            }
+        }
+    }
+}
+
+/// Plays the game of `serve`, returning how it ended
+fn play_session(
+    config: &Opts,
+    book: &OpeningBook,
+    clients: &mut [Client],
+) -> Result<String, String> {
+    let mut selector = Selector::new(config.temperature, config.seed.unwrap());
+    let mut state = new_game(config);
+    loop {
+        if state.is_draw() {
+            broadcast(clients, "draw")?;
+            return Ok("Draw!".into());
+        }
+
+        let player = state.current_player();
+        let column = match clients.iter_mut().find(|client| client.player == player) {
+            Some(client) => {
+                writeln!(client.writer, "your move")
+                    .map_err(|_| format!("player {:?} disconnected", player))?;
+                let mut line = String::new();
+                match client.reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => return Err(format!("player {:?} disconnected", player)),
+                    Ok(_) => match parse_board_column(&line, config.cols) {
+                        Ok(column) => column,
+                        Err(error @ ParseError::ColumnOutOfRange(_)) => {
+                            return Err(format!(
+                                "player {:?} can't play there: {}",
+                                player,
+                                column_error(&error, config.cols)
+                            ))
+                        }
+                        Err(_) => {
+                            return Err(format!("player {:?} sent {:?}", player, line.trim()))
+                        }
+                    },
+                }
+            }
+            None => engine_move(&state, book, config, &mut selector).0,
+        };
+
+        let winner = state
+            .try_move_mut(column)
+            .map_err(|error| format!("player {:?} can't play there: {}", player, error))?;
+        broadcast(
+            clients,
+            &format!("played {}\n{}", column, state.print_board().trim_end()),
+        )?;
+        if winner.is_some() {
+            broadcast(clients, &format!("winner {:?}", player))?;
+            return Ok(format!("Player {:?} wins!", player));
+        }
+    }
+}
+
+/// Sends `message` as a line to every client
+fn broadcast(clients: &mut [Client], message: &str) -> Result<(), String> {
+    for client in clients {
+        writeln!(client.writer, "{}", message)
+            .map_err(|_| format!("player {:?} disconnected", client.player))?;
+    }
+    Ok(())
+}
+
+/// Prints every legal move of the position after `moves` with its eval,
+/// best first, marking the best move and the moves that win or lose by force
+fn analyze(config: &Opts, moves: &str) {
+    let state = match replay(new_game(config), &split_moves(moves), config.cols) {
+        Ok(state) => state,
+        Err(error) => {
+            eprintln!("Invalid moves: {}", error);
+            std::process::exit(1);
+        }
+    };
+    println!("{}", state);
+    if state.is_terminal() {
+        println!("The game is over");
+        return;
+    }
+    let trace = trace_search(&state, config.depth);
+    println!(
+        "{:<10}{:<24}{:>9}{:>11}",
+        "Move", "Eval", "Result in", "Heuristic"
+    );
+    for column in &trace.moves {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+        let note = if column.forced_win {
+            "forced win <- best"
+        } else if column.selected {
+            "<- best"
+        } else if column.pruned {
+            "pruned, allows an immediate reply"
+        } else {
+            ""
+        };
+        let line = format!(
+            "{:<10}{:<24}{:>9}{:>11}  {}",
+            format!("Column {}:", column.m),
+            column.eval.to_string(),
+            optional(column.distance.map(|plies| plies.to_string())),
+            optional(column.heuristic.map(|score| score.to_string())),
+            note
+        );
+        println!("{}", line.trim_end());
     }
 }
+
+/// Prints each move of a game with its eval and the best move's,
+/// marking moves that give away the result
+fn review(config: &Opts, moves: &str) {
+    let moves: Result<Vec<u8>, String> = split_moves(moves)
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            parse_board_column(column, config.cols)
+                .map_err(|error| format!("move {}: {}", index, column_error(&error, config.cols)))
+        })
+        .collect();
+    let analysis = moves.and_then(|moves| {
+        analyze_game_from(&new_game(config), &moves, config.depth)
+            .map_err(|error| error.to_string())
+    });
+    let analysis = match analysis {
+        Ok(analysis) => analysis,
+        Err(error) => {
+            eprintln!("Invalid moves: {}", error);
+            std::process::exit(1);
+        }
+    };
+    for m in analysis {
+        let blunder = if m.blunder { " ?? blunder" } else { "" };
+        println!(
+            "{}. {:?} plays {}: {}, best {}: {}{}",
+            m.ply + 1,
+            m.player,
+            m.played,
+            m.played_eval,
+            m.best,
+            m.best_eval,
+            blunder
+        );
+    }
+}
+
+/// Line-based protocol for other programs, similar to UCI for chess.
+/// Understands the commands
+/// - `newgame`: back to the empty board
+/// - `position moves <column>...`: the position after these moves from the empty board
+/// - `go [depth <plies>]`: search the position, replying with `info eval <eval> nodes <count>`
+///   (or `info book`) followed by `bestmove <column>`, or `bestmove none` if the game is over
+/// - `quit`
+///
+/// Anything else gets an `info string` warning and is otherwise ignored.
+fn engine_protocol(config: Opts, book: &OpeningBook) {
+    let start = new_game(&config);
+    let mut state = start;
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["quit"] => break,
+            ["newgame"] => state = start,
+            ["position", "moves", moves @ ..] => match replay(start, moves, config.cols) {
+                Ok(position) => state = position,
+                Err(error) => println!("info string invalid position: {}", error),
+            },
+            ["go"] => go(&state, book, config.depth, config.parallel_until_depth),
+            ["go", "depth", depth] => match depth.parse() {
+                Ok(depth) => go(&state, book, depth, config.parallel_until_depth),
+                Err(_) => println!("info string invalid depth: {}", depth),
+            },
+            _ => println!("info string unknown command: {}", line.trim()),
+        }
+    }
+}
+
+/// Plays `moves` from `start` on a board with `columns` columns, only the
+/// last move may win
+fn replay(start: State, moves: &[&str], columns: u8) -> Result<State, String> {
+    let mut state = start;
+    for (index, column) in moves.iter().enumerate() {
+        if state.winner().is_some() {
+            return Err(format!("move {}: {}", index, MoveError::GameOver));
+        }
+        let column = parse_board_column(column, columns)
+            .map_err(|error| format!("move {}: {}", index, column_error(&error, columns)))?;
+        state
+            .try_move_mut(column)
+            .map_err(|error| format!("move {}: {}", index, error))?;
+    }
+    Ok(state)
+}
+
+/// The moves of `--analyze` and `--analyze-game`, one character each
+fn split_moves(moves: &str) -> Vec<&str> {
+    moves
+        .char_indices()
+        .map(|(index, c)| &moves[index..index + c.len_utf8()])
+        .collect()
+}
+
+/// Answers the `go` command of `engine_protocol`
+fn go(state: &State, book: &OpeningBook, depth: u8, parallel_until_depth: u8) {
+    if state.is_terminal() {
+        println!("bestmove none");
+        return;
+    }
+    let (next_move, eval, stats) =
+        find_next_move_with_book(state, book, depth, parallel_until_depth);
+    match eval {
+        Some(eval) => println!("info eval {:?} nodes {}", eval, stats.nodes),
+        None => println!("info book"),
+    }
+    println!("bestmove {}", next_move[0]);
+}
+
+/// Asks for a column of the board with `columns` columns until a playable
+/// one is entered. Returns `None` if the player quits or the input ends.
+fn read_column(state: &State, columns: u8) -> Option<u8> {
+    let mut line = String::new();
+    loop {
+        print!(
+            "Player {:?}, your move (0-{} or a-{}, q to quit): ",
+            state.current_player(),
+            columns - 1,
+            column_letter(columns - 1)
+        );
+        stdout().flush().unwrap();
+        line.clear();
+        if stdin().lock().read_line(&mut line).ok()? == 0 || line.trim() == "q" {
+            println!("Quitting");
+            return None;
+        }
+        let column = match parse_board_column(&line, columns) {
+            Ok(column) => column,
+            Err(error @ ParseError::ColumnOutOfRange(_)) => {
+                println!("Can't play there: {}", column_error(&error, columns));
+                continue;
+            }
+            Err(error) => {
+                println!(
+                    "Please enter a column number: {}",
+                    column_error(&error, columns)
+                );
+                continue;
+            }
+        };
+        match state.play(column) {
+            Ok(_) => return Some(column),
+            Err(error) => println!("Can't play there: {}", error),
+        }
+    }
+}
+
+/// Board for printing, colored when writing to a terminal
+#[cfg(feature = "color")]
+fn render(state: &State, last_move: u8) -> String {
+    use std::io::IsTerminal;
+
+    if std::io::stdout().is_terminal() {
+        state.render_colored(Some(last_move))
+    } else {
+        state.to_string()
+    }
+}
+
+#[cfg(not(feature = "color"))]
+fn render(state: &State, _last_move: u8) -> String {
+    state.to_string()
+}
+
+/// Board with the pieces of the winning line drawn as `*`
+fn render_win(state: &State) -> String {
+    let winning_cells = state.winning_cells().unwrap_or_default();
+    state.board().render_with(|column, row| {
+        if winning_cells.contains(&(column, row)) {
+            "*"
+        } else {
+            state[(column, row)].glyph()
+        }
+    })
+}
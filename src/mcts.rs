@@ -0,0 +1,161 @@
+//! Monte Carlo tree search: instead of a heuristic, positions are judged by
+//! how random games continuing from them end
+
+use alloc::vec::Vec;
+
+use smallvec::SmallVec;
+
+use crate::{rollout::rollout, MoveResult, Position, COLUMNS};
+
+/// Weight of exploring rarely visited moves against playing the best
+/// ones so far, the usual choice for results between 0 and 1
+const EXPLORATION: f32 = core::f32::consts::SQRT_2;
+
+/// Position reached by a move in the tree
+#[derive(Clone, Copy)]
+enum Reached<P> {
+    State(P),
+    /// The move won the game
+    Victory,
+}
+
+struct Node<P: Position> {
+    parent: Option<usize>,
+    /// Move leading here from the parent
+    m: Option<P::Move>,
+    reached: Reached<P>,
+    children: SmallVec<[usize; COLUMNS as usize]>,
+    /// Moves without a child yet, the next one to try last
+    untried: SmallVec<[P::Move; COLUMNS as usize]>,
+    visits: u32,
+    /// Sum of the results for the player who made the move leading here:
+    /// 1 for a win, 0.5 for a draw
+    wins: f32,
+}
+
+impl<P: Position> Node<P> {
+    fn new(parent: Option<usize>, m: Option<P::Move>, reached: Reached<P>) -> Self {
+        let mut untried = match reached {
+            Reached::State(state) => state.legal_moves(),
+            Reached::Victory => SmallVec::new(),
+        };
+        // Tried in search order, e.g. for columns from the center outwards
+        untried.reverse();
+        Self {
+            parent,
+            m,
+            reached,
+            children: SmallVec::new(),
+            untried,
+            visits: 0,
+            wins: 0.,
+        }
+    }
+
+    /// Upper confidence bound of the result for the player making the move
+    /// leading here, given the visits of the parent
+    fn uct(&self, parent_visits: u32) -> f32 {
+        let visits = self.visits as f32;
+        self.wins / visits + EXPLORATION * ((parent_visits as f32).ln() / visits).sqrt()
+    }
+}
+
+/// Move that UCT (upper confidence bounds applied to trees) picks after
+/// `iterations` random games from `state`, seeded with `seed`. Each
+/// iteration descends the tree by the most promising moves, adds one move
+/// and plays randomly from there. The move visited the most wins, `None` if
+/// there are no moves left. Doesn't need a heuristic, but plays weaker than
+/// `find_next_move` unless given many iterations.
+pub fn mcts_best_move<P: Position>(state: &P, iterations: u32, seed: u64) -> Option<P::Move> {
+    let mut rng = oorandom::Rand32::new(seed);
+    let mut nodes = Vec::new();
+    nodes.push(Node::new(None, None, Reached::State(*state)));
+
+    for _ in 0..iterations {
+        // Selection
+        let mut index = 0;
+        while nodes[index].untried.is_empty() && !nodes[index].children.is_empty() {
+            let parent_visits = nodes[index].visits;
+            index = *nodes[index]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    nodes[a]
+                        .uct(parent_visits)
+                        .partial_cmp(&nodes[b].uct(parent_visits))
+                        .unwrap()
+                })
+                .unwrap();
+        }
+
+        // Expansion
+        if let Reached::State(state) = nodes[index].reached {
+            while let Some(m) = nodes[index].untried.pop() {
+                let reached = match state.apply(m) {
+                    MoveResult::Impossible => continue,
+                    MoveResult::Victory => Reached::Victory,
+                    MoveResult::State(next) => Reached::State(next),
+                };
+                nodes.push(Node::new(Some(index), Some(m), reached));
+                let child = nodes.len() - 1;
+                nodes[index].children.push(child);
+                index = child;
+                break;
+            }
+        }
+
+        // Simulation, for the player who made the last move
+        let mut result = match nodes[index].reached {
+            Reached::Victory => 1.,
+            Reached::State(state) => match rollout(&state, &mut rng) {
+                Some(true) => 0.,
+                Some(false) => 1.,
+                None => 0.5,
+            },
+        };
+
+        // Backpropagation, the players alternate on the way up
+        let mut node = Some(index);
+        while let Some(index) = node {
+            nodes[index].visits += 1;
+            nodes[index].wins += result;
+            result = 1. - result;
+            node = nodes[index].parent;
+        }
+    }
+
+    // Ties go to the move searched first
+    let mut best: Option<&Node<P>> = None;
+    for &child in &nodes[0].children {
+        if best.is_none_or(|best| nodes[child].visits > best.visits) {
+            best = Some(&nodes[child]);
+        }
+    }
+    best.and_then(|best| best.m)
+}
+
+#[test]
+fn test_finds_immediate_win() {
+    use crate::{state3d::play_3d, Column, State};
+
+    // Only column 0 wins right away
+    let state = State::from_moves(&[0, 6, 0, 6, 0, 5]).unwrap();
+    for seed in 0..10 {
+        assert_eq!(mcts_best_move(&state, 200, seed), Some(Column(0)));
+        assert_eq!(
+            mcts_best_move(&crate::BitBoard::from(state), 200, seed),
+            Some(Column(0))
+        );
+    }
+
+    // O completes the space diagonal of the 3D variant
+    let state = play_3d(&[0, 5, 5, 10, 10, 15, 10, 15, 15, 3]);
+    assert_eq!(mcts_best_move(&state, 500, 1), Some(15));
+
+    // X has to block column 4, where O would complete the bottom row
+    let state = State::from_moves(&[1, 0, 2, 6, 3]).unwrap();
+    assert_eq!(mcts_best_move(&state, 2000, 1), Some(Column(4)));
+
+    assert_eq!(mcts_best_move(&crate::drawn_board(), 100, 1), None);
+    assert!(mcts_best_move(&State::empty(), 0, 1).is_none());
+}
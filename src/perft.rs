@@ -0,0 +1,82 @@
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+use crate::{MoveResult, Position, State};
+
+/// Number of move sequences of length `depth` starting at `state`, for
+/// checking move generation. Sequences ending early with a win or a full
+/// board count once.
+pub fn perft(state: &State, depth: u8) -> u64 {
+    if depth == 0 || state.is_full() {
+        return 1;
+    }
+    state
+        .legal_moves()
+        .map(|column| match state.try_move(column) {
+            MoveResult::State(next) => perft(&next, depth - 1),
+            MoveResult::Victory => 1,
+            MoveResult::Impossible => unreachable!(),
+        })
+        .sum()
+}
+
+/// Every position reachable from the empty board in exactly `ply` moves in
+/// which the game isn't over, each once up to mirroring. Positions are yielded
+/// as their canonical form, the one the transposition table stores, and told
+/// apart by its Zobrist hash.
+pub fn positions_at_ply(ply: u8) -> impl Iterator<Item = State> {
+    let mut positions = vec![State::empty()];
+    for _ in 0..ply {
+        let mut seen = BTreeSet::new();
+        positions = positions
+            .iter()
+            .flat_map(|state| {
+                state
+                    .legal_moves()
+                    .filter_map(move |column| match state.try_move(column) {
+                        MoveResult::State(next) if !next.is_full() => Some(next.canonical().0),
+                        _ => None,
+                    })
+            })
+            .filter(|next| seen.insert(next.hash))
+            .collect::<Vec<_>>();
+    }
+    positions.into_iter()
+}
+
+#[test]
+fn test_perft() {
+    let empty = State::empty();
+    assert_eq!(perft(&empty, 0), 1);
+    assert_eq!(perft(&empty, 1), 7);
+    assert_eq!(perft(&empty, 2), 49);
+    // Before the 7th move, no column can be full and no one can have won
+    assert_eq!(perft(&empty, 6), 7u64.pow(6));
+    // The 7th move can't go into a column that all previous moves went into
+    assert_eq!(perft(&empty, 7), 7u64.pow(7) - 7);
+
+    assert_eq!(perft(&crate::drawn_board(), 3), 1);
+    // One column left, the first move in it wins
+    let state = State::from_moves(&[0, 1, 0, 1, 0, 1]).unwrap();
+    assert_eq!(perft(&state, 1), 7);
+    assert_eq!(perft(&state, 2), 1 + 6 * 7);
+}
+
+#[test]
+fn test_positions_at_ply() {
+    assert_eq!(positions_at_ply(0).collect::<Vec<_>>(), [State::empty()]);
+    // Columns 0 to 3, the others are their mirror images
+    assert_eq!(positions_at_ply(1).count(), 4);
+    // 24 pairs of mirror images among the 49 sequences, plus 3 then 3
+    assert_eq!(positions_at_ply(2).count(), 24 + 1);
+    for ply in 0..6 {
+        let positions: Vec<_> = positions_at_ply(ply).collect();
+        let hashes: BTreeSet<_> = positions.iter().map(|state| state.hash).collect();
+        assert_eq!(hashes.len(), positions.len());
+        for state in positions {
+            assert_eq!(state.turn(), ply as usize);
+            assert_eq!(state.canonical().0, state);
+            assert_eq!(state.hash, state.zobrist_hash());
+            assert_eq!(state.winner(), None);
+        }
+    }
+}
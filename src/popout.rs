@@ -0,0 +1,171 @@
+//! Pop Out variant: instead of dropping a piece, a player may remove one of
+//! their own pieces from the bottom row, moving the rest of the column down.
+
+use crate::{Cell, Column, MoveResult, State, COLUMNS, ROWS};
+
+/// Move in the Pop Out variant
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PopMove {
+    /// Drop a piece into the column, as in the regular game
+    Drop(u8),
+    /// Remove the own piece at the bottom of the column
+    Pop(u8),
+}
+
+impl State {
+    /// Plays a Pop Out move. Popping is impossible unless the bottom piece of
+    /// the column belongs to the side to move and no cell in it is blocked.
+    /// A pop can complete lines of both players. If any of them is the
+    /// mover's, they win. Otherwise, the opponent is the `winner` of the
+    /// resulting state.
+    pub fn apply(&self, m: PopMove) -> MoveResult {
+        match m {
+            PopMove::Drop(column) => {
+                Column::new(column).map_or(MoveResult::Impossible, |column| self.try_move(column))
+            }
+            PopMove::Pop(column) => self.pop(column),
+        }
+    }
+
+    fn pop(&self, column: u8) -> MoveResult {
+        if column >= COLUMNS || self[(column, ROWS - 1)] != Cell::Set(self.player) {
+            return MoveResult::Impossible;
+        }
+        // Blocked cells can't move down
+        if (0..ROWS).any(|row| self[(column, row)] == Cell::Blocked) {
+            return MoveResult::Impossible;
+        }
+        let mut board = self.board;
+        for row in (1..ROWS).rev() {
+            board[(column, row)] = board[(column, row - 1)];
+        }
+        board[(column, 0)] = Cell::Empty;
+        let next = State {
+            win_length: self.win_length,
+            ..State::from_board(board.0, self.player.other())
+        };
+
+        // All pieces that moved are in this column
+        let completes_line = (0..ROWS).any(|row| {
+            next[(column, row)] == Cell::Set(self.player)
+                && next.check_win_through(column, row, self.player)
+        });
+        if completes_line {
+            MoveResult::Victory
+        } else {
+            MoveResult::State(next)
+        }
+    }
+}
+
+#[test]
+fn test_pop_shifts_column() {
+    use crate::Player;
+
+    let state = State::from_moves(&[0, 0, 0, 1]).unwrap();
+    let next = match state.apply(PopMove::Pop(0)) {
+        MoveResult::State(next) => next,
+        result => panic!("{:?}", result),
+    };
+    assert_eq!(next[(0, ROWS - 1)], Cell::Set(Player::X));
+    assert_eq!(next[(0, ROWS - 2)], Cell::Set(Player::O));
+    assert_eq!(next[(0, ROWS - 3)], Cell::Empty);
+    assert_eq!(next[(1, ROWS - 1)], Cell::Set(Player::X));
+    assert_eq!(next.current_player(), Player::X);
+    assert_eq!(next.turn(), 3);
+
+    // Only the own pieces can be popped
+    assert_eq!(state.apply(PopMove::Pop(1)), MoveResult::Impossible);
+    assert_eq!(state.apply(PopMove::Pop(2)), MoveResult::Impossible);
+    assert_eq!(state.apply(PopMove::Pop(COLUMNS)), MoveResult::Impossible);
+    assert_eq!(state.apply(PopMove::Drop(2)), state.try_move(Column(2)));
+    assert_eq!(state.apply(PopMove::Drop(COLUMNS)), MoveResult::Impossible);
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_pop_lines() {
+    use crate::{Cell::*, Player::*};
+
+    // Popping column 0 moves its O down next to the three others
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(X), Set(O), Set(O), Set(O), Empty,  Set(X), Empty ],
+            [Set(O), Set(X), Set(O), Set(X), Empty,  Set(X), Set(X)],
+        ],
+        O,
+    );
+    assert_eq!(state.winner(), None);
+    assert_eq!(state.apply(PopMove::Pop(0)), MoveResult::Victory);
+    assert!(matches!(state.apply(PopMove::Pop(2)), MoveResult::State(next) if next.winner().is_none()));
+
+    // Popping the piece of a line breaks it up
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(X), Empty,  Empty,  Empty,  Empty,  Set(X), Empty ],
+            [Set(O), Set(O), Set(O), Set(O), Empty,  Set(X), Set(X)],
+        ],
+        O,
+    );
+    assert_eq!(state.winner(), Some(O));
+    assert!(matches!(state.apply(PopMove::Pop(0)), MoveResult::State(next) if next.winner().is_none()));
+
+    // A line of only the opponent is their win
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(X), Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Set(X), Set(X), Set(X), Empty,  Empty,  Empty ],
+            [Set(O), Set(O), Set(X), Set(O), Empty,  Set(O), Empty ],
+        ],
+        O,
+    );
+    assert!(matches!(state.apply(PopMove::Pop(0)), MoveResult::State(next) if next.winner() == Some(X)));
+
+    // The O moving down in column 0 completes a diagonal, away from the
+    // bottom row where the piece was removed
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Set(O), Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Set(O), Set(X), Empty,  Empty,  Empty ],
+            [Set(O), Set(O), Set(X), Set(O), Empty,  Empty,  Empty ],
+            [Set(X), Set(X), Set(O), Set(X), Empty,  Empty,  Empty ],
+            [Set(O), Set(X), Set(X), Set(O), Set(X), Empty,  Empty ],
+        ],
+        O,
+    );
+    assert_eq!(state.winner(), None);
+    assert!(state.check_win_through(0, 4, O));
+    assert_eq!(state.apply(PopMove::Pop(0)), MoveResult::Victory);
+
+    // The column keeps its order, so popping can't join pieces in it into
+    // a vertical line
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(X), Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Set(X), Empty,  Empty,  Empty,  Set(X), Set(X)],
+        ],
+        O,
+    );
+    let next = match state.apply(PopMove::Pop(0)) {
+        MoveResult::State(next) => next,
+        result => panic!("{:?}", result),
+    };
+    assert_eq!(next.winner(), None);
+    assert!(!next.check_win_through(0, ROWS - 1, X));
+}
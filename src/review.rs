@@ -0,0 +1,134 @@
+//! Looking back at a finished game: how each move compares to the best one
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::{rank_moves, Column, Eval, MoveError, Player, SequenceError, State};
+
+/// One move of a game reviewed by `analyze_game`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveAnalysis {
+    /// Index of the move in the game
+    pub ply: usize,
+    pub player: Player,
+    pub played: Column,
+    pub played_eval: Eval,
+    /// The move the search prefers, with the eval of the position
+    pub best: Column,
+    pub best_eval: Eval,
+    /// Whether the move gave away the result: a win for anything else, or a
+    /// draw or open position for a loss. Slower wins and faster losses
+    /// aren't blunders, only worse than the best move.
+    pub blunder: bool,
+}
+
+/// Result class of an eval: loss, anything undecided, win
+fn outcome(eval: Eval) -> i8 {
+    match eval {
+        Eval::AssuredLoss(_) => -1,
+        Eval::Draw | Eval::Neutral(_) => 0,
+        Eval::AssuredVictory(_) | Eval::ImmediateVictory => 1,
+    }
+}
+
+/// Replays `moves` from the empty board and compares each move to the best
+/// one at `depth`, as ranked by `rank_moves`. As with `State::from_moves`,
+/// only the last move may win.
+pub fn analyze_game(moves: &[u8], depth: u8) -> Result<Vec<MoveAnalysis>, SequenceError> {
+    analyze_game_from(&State::empty(), moves, depth)
+}
+
+/// Like `analyze_game`, but replays `moves` from `start`, e.g. a board with
+/// blocked cells
+pub fn analyze_game_from(
+    start: &State,
+    moves: &[u8],
+    depth: u8,
+) -> Result<Vec<MoveAnalysis>, SequenceError> {
+    let mut state = *start;
+    let mut analysis = Vec::with_capacity(moves.len());
+    for (ply, &column) in moves.iter().enumerate() {
+        if state.winner().is_some() {
+            return Err(SequenceError::Move(ply, MoveError::GameOver));
+        }
+        let error = |error| SequenceError::Move(ply, error);
+        let played = Column::try_from(column).map_err(error)?;
+        let ranking = rank_moves(&state, depth);
+        let &(_, played_eval) = ranking
+            .iter()
+            .find(|&&(m, _)| m == played)
+            .ok_or(error(MoveError::ColumnFull))?;
+        let (best, best_eval) = ranking[0];
+        analysis.push(MoveAnalysis {
+            ply,
+            player: state.current_player(),
+            played,
+            played_eval,
+            best,
+            best_eval,
+            blunder: outcome(played_eval) < outcome(best_eval),
+        });
+        state.try_move_mut(column).map_err(error)?;
+    }
+    Ok(analysis)
+}
+
+#[test]
+fn test_analyze_game() {
+    use crate::ROWS;
+
+    // O could win in column 0 but plays column 2 instead, X then wins in column 1
+    let moves = [0, 1, 0, 1, 0, 1, 2, 1];
+    let analysis = analyze_game(&moves, 4).unwrap();
+    assert_eq!(analysis.len(), moves.len());
+    for (index, analysis) in analysis.iter().enumerate() {
+        assert_eq!(analysis.ply, index);
+        assert_eq!(u8::from(analysis.played), moves[index]);
+        assert!(analysis.played_eval <= analysis.best_eval);
+    }
+    let thrown = analysis[6];
+    assert_eq!(thrown.player, Player::O);
+    assert_eq!(
+        (thrown.best, thrown.best_eval),
+        (Column(0), Eval::ImmediateVictory)
+    );
+    assert_eq!(thrown.played_eval, Eval::AssuredLoss(2));
+    assert!(thrown.blunder);
+    // X had to block column 0 before, and wins with the last move
+    assert!(analysis[5].blunder);
+    assert!(!analysis[7].blunder);
+    assert_eq!(analysis[7].played_eval, Eval::ImmediateVictory);
+    let blunders: Vec<_> = analysis
+        .iter()
+        .filter(|m| m.blunder)
+        .map(|m| m.ply)
+        .collect();
+    assert_eq!(blunders, [5, 6]);
+
+    // A blocked cell leaves room for one piece less in its column
+    let mut start = State::empty();
+    start.block_cell(1).unwrap();
+    let moves = [1; ROWS as usize];
+    assert_eq!(
+        analyze_game_from(&start, &moves, 2),
+        Err(SequenceError::Move(
+            ROWS as usize - 1,
+            MoveError::ColumnFull
+        ))
+    );
+    assert_eq!(analyze_game(&moves, 2).unwrap().len(), moves.len());
+
+    assert!(analyze_game(&[], 4).unwrap().is_empty());
+    assert_eq!(
+        analyze_game(&[0, 0, 0, 0, 0, 0, 0], 2),
+        Err(SequenceError::Move(6, MoveError::ColumnFull))
+    );
+    assert_eq!(
+        analyze_game(&[3, 7], 2),
+        Err(SequenceError::Move(1, MoveError::ColumnOutOfRange))
+    );
+    assert_eq!(
+        analyze_game(&[0, 1, 0, 1, 0, 1, 0, 1], 2),
+        Err(SequenceError::Move(7, MoveError::GameOver))
+    );
+}
@@ -0,0 +1,109 @@
+//! Games played out with random moves, e.g. for Monte Carlo evaluation
+
+use crate::{MoveResult, Outcome, Position, State};
+
+/// Source of random numbers for rollouts, so they don't depend on one
+/// particular generator
+pub trait RngLike {
+    /// Uniformly distributed number below `n`, which isn't 0
+    fn below(&mut self, n: u32) -> u32;
+}
+
+impl RngLike for oorandom::Rand32 {
+    fn below(&mut self, n: u32) -> u32 {
+        self.rand_range(0..n)
+    }
+}
+
+impl RngLike for oorandom::Rand64 {
+    fn below(&mut self, n: u32) -> u32 {
+        self.rand_range(0..n as u64) as u32
+    }
+}
+
+impl State {
+    /// Plays uniformly random columns until one of them wins or no column
+    /// is left. A state that has already been won is returned as is.
+    pub fn play_random_game(&self, rng: &mut impl RngLike) -> Outcome {
+        if let Some(winner) = self.winner() {
+            return Outcome::Win(winner);
+        }
+        match rollout(self, rng) {
+            Some(true) => Outcome::Win(self.player),
+            Some(false) => Outcome::Win(self.player.other()),
+            None => Outcome::Draw,
+        }
+    }
+}
+
+/// Plays uniformly random moves until one of them wins or none is left.
+/// `Some(true)` if the side to move at `state` wins, `Some(false)` if the
+/// opponent does, `None` for a draw.
+pub(crate) fn rollout<P: Position>(state: &P, rng: &mut impl RngLike) -> Option<bool> {
+    let mut state = *state;
+    let mut side_to_move_won = true;
+    loop {
+        let moves = state.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        match state.apply(moves[rng.below(moves.len() as u32) as usize]) {
+            MoveResult::State(next) => state = next,
+            MoveResult::Victory => return Some(side_to_move_won),
+            MoveResult::Impossible => unreachable!(),
+        }
+        side_to_move_won = !side_to_move_won;
+    }
+}
+
+#[test]
+fn test_play_random_game() {
+    use crate::{Game, Player, COLUMNS};
+
+    /// Counts the moves the generator is asked for
+    struct Counting(oorandom::Rand32, usize);
+
+    impl RngLike for Counting {
+        fn below(&mut self, n: u32) -> u32 {
+            self.1 += 1;
+            self.0.below(n)
+        }
+    }
+
+    let mut outcomes = [0; 3];
+    for seed in 0..500 {
+        let mut rng = Counting(oorandom::Rand32::new(seed), 0);
+        let outcome = State::empty().play_random_game(&mut rng);
+        assert!((1..=(COLUMNS * crate::ROWS) as usize).contains(&rng.1));
+        outcomes[match outcome {
+            Outcome::Win(Player::O) => 0,
+            Outcome::Win(Player::X) => 1,
+            Outcome::Draw => 2,
+        }] += 1;
+
+        // Replaying the same moves gives the same outcome
+        let mut game = Game::new(State::empty());
+        let mut rng = oorandom::Rand32::new(seed);
+        while game.outcome().is_none() {
+            let moves = Position::legal_moves(game.state());
+            let column = moves[rng.below(moves.len() as u32) as usize];
+            game.play(column.into()).unwrap();
+        }
+        assert_eq!(game.outcome(), Some(outcome));
+        assert_eq!(
+            State::empty().play_random_game(&mut oorandom::Rand64::new(seed.into())),
+            State::empty().play_random_game(&mut oorandom::Rand64::new(seed.into())),
+        );
+    }
+    assert!(outcomes[0] > 0 && outcomes[1] > 0);
+
+    assert_eq!(
+        crate::drawn_board().play_random_game(&mut oorandom::Rand32::new(0)),
+        Outcome::Draw
+    );
+    let won = State::from_moves(&[0, 1, 0, 1, 0, 1, 0]).unwrap();
+    assert_eq!(
+        won.play_random_game(&mut oorandom::Rand32::new(0)),
+        Outcome::Win(Player::O)
+    );
+}
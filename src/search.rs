@@ -0,0 +1,2147 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::Debug,
+    hash::Hash,
+    ops::ControlFlow,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use smallvec::{smallvec, SmallVec};
+
+#[cfg(feature = "std")]
+use crate::SystemClock;
+use crate::{
+    table::{Bound, Entry},
+    BitBoard, Clock, Column, EvalWeights, MoveResult, OpeningBook, Player, State, State3D,
+    TranspositionTable, COLUMNS, COLUMNS_3D, ROWS, SIZE_3D,
+};
+
+/// Result of a position for the side to move.
+/// Distances are given in plies, i.e. moves of either player.
+///
+/// Evals are ordered by how good they are for the side to move:
+/// `AssuredLoss < Draw < Neutral < AssuredVictory < ImmediateVictory`, with
+/// later losses, higher scores and sooner wins being better.
+/// The search itself ranks a `Draw` like a `Neutral(0)`, see `search_key`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Eval {
+    /// The next move wins
+    ImmediateVictory,
+    /// Can force a win, the winning move being the given number of plies away
+    AssuredVictory(u8),
+    /// The opponent can force a win, their winning move being the given number of plies away
+    AssuredLoss(u8),
+    Draw,
+    /// No result within the search depth, with the heuristic score of the
+    /// position the search ended at
+    Neutral(i32),
+}
+
+/// Score of a win without any moves left to make, above any heuristic score
+const VICTORY: i32 = 1_000_000;
+
+impl Eval {
+    /// Outcome for the side to move, used as alpha-beta bound.
+    /// Sooner wins and later losses score higher.
+    /// Negating the score gives the outcome for the opponent.
+    pub(crate) fn score(self) -> i32 {
+        match self {
+            Eval::ImmediateVictory => VICTORY - 1,
+            Eval::AssuredVictory(plies) => VICTORY - plies as i32,
+            Eval::Draw => 0,
+            Eval::Neutral(score) => score.clamp(-VICTORY / 2, VICTORY / 2),
+            Eval::AssuredLoss(plies) => plies as i32 - VICTORY,
+        }
+    }
+
+    /// Order of the evals in the search. Negating the eval for the opponent
+    /// has to reverse it, which a `Draw` below every `Neutral` wouldn't
+    /// allow, so it ranks like a `Neutral(0)` here: a `Neutral` with a
+    /// negative score is worse than a draw. To keep the game open,
+    /// `Neutral(0)` is still preferred over a `Draw`. Evals with the same
+    /// score are ordered as by `Ord`, `Neutral` evals beyond the clamped
+    /// range by their actual score.
+    fn search_key(self) -> (i32, bool, i32) {
+        match self {
+            Eval::ImmediateVictory => (self.score(), true, 0),
+            Eval::Neutral(score) => (self.score(), true, score),
+            _ => (self.score(), false, 0),
+        }
+    }
+
+    /// Negamax step: eval of a move given the eval of the resulting state for the opponent
+    fn for_previous_player(self) -> Self {
+        match self {
+            Eval::ImmediateVictory => Eval::AssuredLoss(2),
+            Eval::AssuredVictory(plies) => Eval::AssuredLoss(plies + 1),
+            Eval::AssuredLoss(plies) => Eval::AssuredVictory(plies + 1),
+            Eval::Draw => Eval::Draw,
+            Eval::Neutral(score) => Eval::Neutral(-score),
+        }
+    }
+}
+
+impl core::fmt::Display for Eval {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Eval::ImmediateVictory => write!(f, "immediate win"),
+            Eval::AssuredVictory(plies) => write!(f, "winning in {} plies", plies),
+            Eval::AssuredLoss(plies) => write!(f, "losing in {} plies", plies),
+            Eval::Draw => write!(f, "drawn"),
+            Eval::Neutral(score) => write!(f, "unclear (score {})", score),
+        }
+    }
+}
+
+impl Ord for Eval {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        let rank = |eval: &Eval| match *eval {
+            Eval::AssuredLoss(plies) => (0, plies as i32),
+            Eval::Draw => (1, 0),
+            Eval::Neutral(score) => (2, score),
+            Eval::AssuredVictory(plies) => (3, -(plies as i32)),
+            Eval::ImmediateVictory => (4, 0),
+        };
+        rank(self).cmp(&rank(other))
+    }
+}
+
+impl PartialOrd for Eval {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl State {
+    /// Eval of the position for the side to move, without picking a move.
+    /// A position that has already been won is `AssuredLoss(0)`
+    /// for the side to move (or `AssuredVictory(0)` if it's their line),
+    /// a full board without a winner is a `Draw`.
+    pub fn evaluate(&self, depth: u8) -> Eval {
+        self.terminal_value()
+            .unwrap_or_else(|| find_next_move(self, depth, DEFAULT_PARALLEL_UNTIL_DEPTH).1)
+    }
+
+    /// Result of the position with perfect play, searching until the board
+    /// is full. Wins are as fast and losses as slow as possible.
+    /// Can take very long unless most of the board is already filled.
+    pub fn solve(&self) -> Eval {
+        self.terminal_value().unwrap_or_else(|| {
+            let empty_cells = self.empty_cells() as u8;
+            // Parallel branches don't share bounds, which would cost far more
+            // than it gains at this depth
+            let mut table = TranspositionTable::default();
+            find_next_move_with_table(self, empty_cells, &mut table).1
+        })
+    }
+
+    /// The only move that wins by force, making the position a puzzle with a
+    /// single solution. `None` if the side to move can't force a win or has
+    /// several ways to, however much slower. Solves the position after each
+    /// move, so it takes even longer than `solve`.
+    pub fn unique_winning_move(&self) -> Option<Column> {
+        if self.is_terminal() {
+            return None;
+        }
+        // The solves share their table, each reaches the positions of the others
+        let mut table = TranspositionTable::default();
+        let (next_moves, eval) =
+            find_next_move_with_table(self, self.empty_cells() as u8, &mut table);
+        if !matches!(eval, Eval::ImmediateVictory | Eval::AssuredVictory(_)) {
+            return None;
+        }
+        let best = match next_moves[..] {
+            [best] => best,
+            _ => return None,
+        };
+        // The search only keeps the fastest wins
+        let other_wins =
+            self.legal_moves()
+                .filter(|&column| column != best)
+                .any(|column| match self.try_move(column) {
+                    MoveResult::Victory => true,
+                    MoveResult::State(next) if next.is_full() => false,
+                    MoveResult::State(next) => {
+                        let depth = next.empty_cells() as u8;
+                        matches!(
+                            find_next_move_with_table(&next, depth, &mut table).1,
+                            Eval::AssuredLoss(_)
+                        )
+                    }
+                    MoveResult::Impossible => unreachable!(),
+                });
+        if other_wins {
+            None
+        } else {
+            Some(best)
+        }
+    }
+
+    /// Eval of a position where the game is over, as for `evaluate`, or
+    /// `None` if it goes on
+    pub fn terminal_value(&self) -> Option<Eval> {
+        match self.winner() {
+            Some(winner) if winner == self.player => Some(Eval::AssuredVictory(0)),
+            Some(_) => Some(Eval::AssuredLoss(0)),
+            None if self.is_full() => Some(Eval::Draw),
+            None => None,
+        }
+    }
+}
+
+/// Game the search can run on, e.g. a board representation or a variant.
+/// Positions reached during a search never have a winner: the search stops
+/// at the move completing a line.
+pub trait Position: Copy + Eq + Hash + Send + Sync {
+    /// How a move is described, e.g. the column a piece is dropped into
+    type Move: Copy + Eq + Debug + Send + Sync;
+
+    /// Moves that can be played, in the order they are searched.
+    /// Stronger moves should come first, they lead to earlier cutoffs.
+    fn legal_moves(&self) -> SmallVec<[Self::Move; COLUMNS as usize]>;
+    /// Plays `m` for the side to move
+    fn apply(&self, m: Self::Move) -> MoveResult<Self>;
+    /// Whether the game is over because no moves are left
+    fn is_terminal(&self) -> bool;
+    /// Estimates how promising the position is for the side to move
+    fn heuristic_score(&self, weights: &EvalWeights) -> i32;
+
+    /// Eval of a position whose result is already certain without searching,
+    /// used in place of the heuristic score where the search ends
+    fn forced_eval(&self) -> Option<Eval> {
+        None
+    }
+
+    /// Whether the side to move can win with their next move
+    fn has_immediate_win(&self) -> bool {
+        self.legal_moves()
+            .into_iter()
+            .any(|m| matches!(self.apply(m), MoveResult::Victory))
+    }
+
+    /// The position stored in the transposition table in place of this one,
+    /// and whether it is this one's mirror image. Mirrored positions have
+    /// the same eval, so they can share an entry.
+    fn canonical(&self) -> (Self, bool) {
+        (*self, false)
+    }
+
+    /// The move in the mirror image that corresponds to `m`
+    fn mirror_move(m: Self::Move) -> Self::Move {
+        m
+    }
+
+    /// Whether the position is its own mirror image, so mirrored moves
+    /// have the same eval
+    fn is_symmetric(&self) -> bool {
+        false
+    }
+}
+
+impl Position for State {
+    type Move = Column;
+
+    fn legal_moves(&self) -> SmallVec<[Column; COLUMNS as usize]> {
+        MOVE_ORDER
+            .iter()
+            .copied()
+            .filter(|&column| self.heights[column as usize] < ROWS)
+            .map(Column)
+            .collect()
+    }
+
+    fn apply(&self, column: Column) -> MoveResult<Self> {
+        State::try_move(self, column)
+    }
+
+    fn is_terminal(&self) -> bool {
+        // Cheaper than `State::is_terminal`, positions reached by the search
+        // never have a winner
+        State::is_full(self)
+    }
+
+    fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+        State::heuristic_score(self, weights)
+    }
+
+    fn forced_eval(&self) -> Option<Eval> {
+        forced_eval(self.player, self.forced_win_threats())
+    }
+
+    fn has_immediate_win(&self) -> bool {
+        !self.immediate_threats(self.player).is_empty()
+    }
+
+    fn canonical(&self) -> (Self, bool) {
+        let mirrored = self.mirrored();
+        if mirrored.board < self.board {
+            (mirrored, true)
+        } else {
+            (*self, false)
+        }
+    }
+
+    fn mirror_move(column: Column) -> Column {
+        State::mirror_move(column)
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.mirrored().board == self.board
+    }
+}
+
+impl Position for BitBoard {
+    type Move = Column;
+
+    fn legal_moves(&self) -> SmallVec<[Column; COLUMNS as usize]> {
+        MOVE_ORDER
+            .iter()
+            .copied()
+            .filter(|&column| self.height(column) < ROWS)
+            .map(Column)
+            .collect()
+    }
+
+    fn apply(&self, column: Column) -> MoveResult<Self> {
+        BitBoard::try_move(self, column)
+    }
+
+    fn is_terminal(&self) -> bool {
+        (0..COLUMNS).all(|column| self.height(column) == ROWS)
+    }
+
+    fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+        BitBoard::heuristic_score(self, weights)
+    }
+
+    fn forced_eval(&self) -> Option<Eval> {
+        forced_eval(self.player, self.forced_win_threats())
+    }
+
+    fn canonical(&self) -> (Self, bool) {
+        let mirrored = self.mirrored();
+        if (mirrored.pieces, mirrored.blocked) < (self.pieces, self.blocked) {
+            (mirrored, true)
+        } else {
+            (*self, false)
+        }
+    }
+
+    fn mirror_move(column: Column) -> Column {
+        State::mirror_move(column)
+    }
+
+    fn is_symmetric(&self) -> bool {
+        let mirrored = self.mirrored();
+        (mirrored.pieces, mirrored.blocked) == (self.pieces, self.blocked)
+    }
+}
+
+/// Central columns first, as their cells lie on the most lines
+const MOVE_ORDER_3D: [u8; COLUMNS_3D as usize] =
+    [5, 6, 9, 10, 0, 3, 12, 15, 1, 2, 4, 7, 8, 11, 13, 14];
+
+impl Position for State3D {
+    type Move = u8;
+
+    fn legal_moves(&self) -> SmallVec<[u8; COLUMNS as usize]> {
+        MOVE_ORDER_3D
+            .iter()
+            .copied()
+            .filter(|&column| self.height(column) < SIZE_3D)
+            .collect()
+    }
+
+    fn apply(&self, column: u8) -> MoveResult<Self> {
+        State3D::try_move(self, column)
+    }
+
+    fn is_terminal(&self) -> bool {
+        State3D::is_full(self)
+    }
+
+    fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+        State3D::heuristic_score(self, weights)
+    }
+}
+
+/// Work done by a search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Positions visited, including the start position
+    pub nodes: u64,
+    /// Visited positions at the search depth or where the game is over
+    pub leaf_nodes: u64,
+    /// Visited positions whose eval was taken from the transposition table
+    pub tt_hits: u64,
+    /// Plies from the start position to the deepest visited position
+    pub max_depth_reached: u8,
+    /// Always zero without the `std` feature, unless the search was given a clock
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Adds the work done by another searcher, e.g. of a parallel search
+    fn merge(&mut self, other: &Self) {
+        self.nodes += other.nodes;
+        self.leaf_nodes += other.leaf_nodes;
+        self.tt_hits += other.tt_hits;
+        self.max_depth_reached = self.max_depth_reached.max(other.max_depth_reached);
+    }
+}
+
+/// Eval for `player` to move given the winner of `forced_win_threats`:
+/// either they win with their next move, or the opponent wins with theirs
+fn forced_eval(player: Player, winner: Option<Player>) -> Option<Eval> {
+    winner.map(|winner| {
+        if winner == player {
+            Eval::ImmediateVictory
+        } else {
+            Eval::AssuredLoss(2)
+        }
+    })
+}
+
+/// Best moves, in search order (for columns, most central first).
+/// The order doesn't depend on which moves were searched on which thread,
+/// so the same position and depth always give the same result.
+pub type NextMove<M = Column> = SmallVec<[M; COLUMNS as usize]>;
+
+/// Columns from the center outwards. Central moves tend to be stronger,
+/// so searching them first leads to earlier cutoffs.
+const MOVE_ORDER: [u8; COLUMNS as usize] = {
+    let mut order = [0; COLUMNS as usize];
+    let mut index = 0;
+    // Twice the distance from the center, to stay in integers for even widths
+    let mut distance = (COLUMNS + 1) % 2;
+    while index < COLUMNS as usize {
+        let mut column = 0;
+        while column < COLUMNS {
+            if (2 * column as i16 - (COLUMNS as i16 - 1)).abs() == distance as i16 {
+                order[index] = column;
+                index += 1;
+            }
+            column += 1;
+        }
+        distance += 2;
+    }
+    order
+};
+
+/// Default for the `parallel_until_depth` argument of the search functions.
+/// Positions with more plies left to search than this get their moves searched
+/// in parallel, at the default depth of 8 that's only the root. Each parallel
+/// branch loses the bounds found by its siblings, so parallelizing deeper
+/// positions trades more total work for more threads being busy.
+/// Without the `parallel` feature, every search is serial.
+pub const DEFAULT_PARALLEL_UNTIL_DEPTH: u8 = 7;
+
+/// Settings of an engine for `search`, e.g. one taking part in
+/// `run_tournament`. Built up from the defaults:
+///
+/// ```
+/// # use connect4::{search, SearchConfig, State};
+/// let config = SearchConfig::new().depth(4).parallel(false);
+/// let (next_moves, eval, stats) = search(&State::empty(), &config);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// Plies searched for every move
+    pub depth: u8,
+    /// As for `find_next_move`
+    pub parallel_until_depth: u8,
+    pub weights: EvalWeights,
+    /// Search deeper step by step up to `depth` until the time is up.
+    /// Without the `std` feature, only `search_with_clock` can tell the time.
+    pub time_budget: Option<Duration>,
+    /// Positions the transposition table holds at most
+    pub table_capacity: usize,
+}
+
+impl Default for SearchConfig {
+    /// Same as the binary's defaults
+    fn default() -> Self {
+        Self {
+            depth: 8,
+            parallel_until_depth: DEFAULT_PARALLEL_UNTIL_DEPTH,
+            weights: EvalWeights::default(),
+            time_budget: None,
+            table_capacity: TranspositionTable::<State>::default().capacity(),
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn depth(self, depth: u8) -> Self {
+        Self { depth, ..self }
+    }
+
+    /// Searches with `DEFAULT_PARALLEL_UNTIL_DEPTH` if enabled, serially otherwise
+    pub fn parallel(self, parallel: bool) -> Self {
+        let parallel_until_depth = if parallel {
+            DEFAULT_PARALLEL_UNTIL_DEPTH
+        } else {
+            u8::MAX
+        };
+        Self {
+            parallel_until_depth,
+            ..self
+        }
+    }
+
+    pub fn parallel_until_depth(self, parallel_until_depth: u8) -> Self {
+        Self {
+            parallel_until_depth,
+            ..self
+        }
+    }
+
+    pub fn weights(self, weights: EvalWeights) -> Self {
+        Self { weights, ..self }
+    }
+
+    pub fn time_budget(self, time_budget: Duration) -> Self {
+        Self {
+            time_budget: Some(time_budget),
+            ..self
+        }
+    }
+
+    pub fn table_capacity(self, table_capacity: usize) -> Self {
+        Self {
+            table_capacity,
+            ..self
+        }
+    }
+}
+
+/// Searches with all the settings of `config`. The `find_next_move`
+/// functions are variants of this with a single setting each.
+/// With a time budget, returns the result of the deepest search completed
+/// in time, the stats covering all of them.
+pub fn search<P: Position>(
+    state: &P,
+    config: &SearchConfig,
+) -> (NextMove<P::Move>, Eval, SearchStats) {
+    let mut table = TranspositionTable::new(config.table_capacity);
+    search_with_table(state, config, &mut table)
+}
+
+/// Like `search`, with the table already filled by previous searches
+fn search_with_table<P: Position>(
+    state: &P,
+    config: &SearchConfig,
+    table: &mut TranspositionTable<P>,
+) -> (NextMove<P::Move>, Eval, SearchStats) {
+    let clock = system_clock();
+    let clock = clock.as_ref().map(|clock| clock as &dyn Clock);
+    // A timed search has to finish some search, so it starts shallow
+    let timed = clock.is_some() && config.time_budget.is_some();
+    let first_depth = if timed { 0 } else { config.depth };
+    deepen(state, config, first_depth, clock, table, |_, _, _| {
+        ControlFlow::Continue(())
+    })
+}
+
+/// Evals of `positions` as by `State::evaluate`, but with the settings of
+/// `config`. With the `parallel` feature, the positions are searched in
+/// parallel and each of them serially. The positions searched by the same
+/// thread reuse the memory of one table, cleared before each search so that
+/// the evals match those of separate searches.
+pub fn evaluate_batch(positions: &[State], config: &SearchConfig) -> Vec<Eval> {
+    let config = config.parallel(false);
+    let evaluate =
+        |table: &mut TranspositionTable, state: &State| evaluate_in_table(state, &config, table);
+    let new_table = || TranspositionTable::new(config.table_capacity);
+    #[cfg(feature = "parallel")]
+    return positions.par_iter().map_init(new_table, evaluate).collect();
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut table = new_table();
+        positions
+            .iter()
+            .map(|state| evaluate(&mut table, state))
+            .collect()
+    }
+}
+
+/// Eval of a single position of `evaluate_batch`, in `table` as left by the
+/// previous one
+fn evaluate_in_table(state: &State, config: &SearchConfig, table: &mut TranspositionTable) -> Eval {
+    state.terminal_value().unwrap_or_else(|| {
+        table.clear();
+        search_with_table(state, config, table).1
+    })
+}
+
+/// Like `search`, but deepens step by step from depth 0 even without a time
+/// budget, calling `progress` with the depth, best moves and eval after each
+/// completed step, e.g. to show them while the search goes on. Returning
+/// `ControlFlow::Break` from it keeps the result so far and stops searching.
+pub fn search_with_progress<P: Position>(
+    state: &P,
+    config: &SearchConfig,
+    progress: impl FnMut(u8, &NextMove<P::Move>, Eval) -> ControlFlow<()>,
+) -> (NextMove<P::Move>, Eval, SearchStats) {
+    let clock = system_clock();
+    let clock = clock.as_ref().map(|clock| clock as &dyn Clock);
+    let mut table = TranspositionTable::new(config.table_capacity);
+    deepen(state, config, 0, clock, &mut table, progress)
+}
+
+/// Like `search_with_progress`, but takes the time from `clock`, e.g. a
+/// `MockClock` in tests. Works without the `std` feature as well.
+pub fn search_with_clock<P: Position>(
+    state: &P,
+    config: &SearchConfig,
+    clock: &dyn Clock,
+    progress: impl FnMut(u8, &NextMove<P::Move>, Eval) -> ControlFlow<()>,
+) -> (NextMove<P::Move>, Eval, SearchStats) {
+    let mut table = TranspositionTable::new(config.table_capacity);
+    deepen(state, config, 0, Some(clock), &mut table, progress)
+}
+
+/// The real time, if there is a way to tell it
+#[cfg(feature = "std")]
+fn system_clock() -> Option<SystemClock> {
+    Some(SystemClock::new())
+}
+
+#[cfg(not(feature = "std"))]
+fn system_clock() -> Option<crate::MockClock> {
+    None
+}
+
+/// Iterative deepening from `first_depth` to the depth of `config`.
+/// Without a clock, the time budget is ignored and no time is measured.
+fn deepen<P: Position>(
+    state: &P,
+    config: &SearchConfig,
+    first_depth: u8,
+    clock: Option<&dyn Clock>,
+    table: &mut TranspositionTable<P>,
+    mut progress: impl FnMut(u8, &NextMove<P::Move>, Eval) -> ControlFlow<()>,
+) -> (NextMove<P::Move>, Eval, SearchStats) {
+    let start = clock.map(|clock| clock.now());
+    let deadline = clock
+        .zip(start)
+        .zip(config.time_budget)
+        .map(|((clock, start), budget)| (clock, start + budget));
+
+    let mut first = AlphaBeta::with_config(table, config);
+    let mut result = search_root(state, first_depth, &mut first).unwrap();
+    let mut stats = first.stats;
+    let mut stop = progress(first_depth, &result.0, result.1).is_break();
+    for depth in first_depth + 1..=config.depth {
+        // A search started after the deadline would only be thrown away
+        if stop || deadline.is_some_and(|(clock, deadline)| clock.now() >= deadline) {
+            break;
+        }
+        let mut deeper = AlphaBeta::with_config(table, config);
+        deeper.deadline = deadline;
+        let completed = search_root(state, depth, &mut deeper);
+        stats.merge(&deeper.stats);
+        match completed {
+            Some(completed) => result = completed,
+            None => break,
+        }
+        stop = progress(depth, &result.0, result.1).is_break();
+    }
+    if let Some((clock, start)) = clock.zip(start) {
+        stats.elapsed = clock.now() - start;
+    }
+    (result.0, result.1, stats)
+}
+
+/// Searches `depth` plies ahead. Positions with more than `parallel_until_depth`
+/// plies left are searched in parallel, below that the search is serial.
+pub fn find_next_move<P: Position>(
+    state: &P,
+    depth: u8,
+    parallel_until_depth: u8,
+) -> (NextMove<P::Move>, Eval) {
+    let mut table = TranspositionTable::default();
+    let mut searcher = AlphaBeta::new(&mut table);
+    searcher.parallel_until_depth = parallel_until_depth;
+    search_root(state, depth, &mut searcher).unwrap()
+}
+
+/// Like `find_next_move`, but scores the positions where the search ends
+/// with `weights` instead of the default ones
+pub fn find_next_move_with_weights<P: Position>(
+    state: &P,
+    depth: u8,
+    parallel_until_depth: u8,
+    weights: &EvalWeights,
+) -> (NextMove<P::Move>, Eval) {
+    let mut table = TranspositionTable::default();
+    let mut searcher = AlphaBeta::new(&mut table);
+    searcher.parallel_until_depth = parallel_until_depth;
+    searcher.weights = *weights;
+    search_root(state, depth, &mut searcher).unwrap()
+}
+
+/// Same result as `find_next_move`, but searches on a `BitBoard`
+pub fn find_next_move_bitboard(
+    state: &State,
+    depth: u8,
+    parallel_until_depth: u8,
+) -> (NextMove, Eval) {
+    let mut table = TranspositionTable::default();
+    let mut searcher = AlphaBeta::new(&mut table);
+    searcher.parallel_until_depth = parallel_until_depth;
+    search_root(&BitBoard::from(*state), depth, &mut searcher).unwrap()
+}
+
+/// Like `find_next_move`, additionally reporting the work done
+pub fn find_next_move_with_stats<P: Position>(
+    state: &P,
+    depth: u8,
+    parallel_until_depth: u8,
+) -> (NextMove<P::Move>, Eval, SearchStats) {
+    let clock = system_clock();
+    let mut table = TranspositionTable::default();
+    let mut searcher = AlphaBeta::new(&mut table);
+    searcher.parallel_until_depth = parallel_until_depth;
+    let (next_moves, eval) = search_root(state, depth, &mut searcher).unwrap();
+    let mut stats = searcher.stats;
+    if let Some(clock) = clock {
+        stats.elapsed = clock.now();
+    }
+    (next_moves, eval, stats)
+}
+
+/// Like `find_next_move_with_stats`, but if the position is in `book`, returns
+/// the book reply without searching. Book replies have no eval.
+pub fn find_next_move_with_book(
+    state: &State,
+    book: &OpeningBook,
+    depth: u8,
+    parallel_until_depth: u8,
+) -> (NextMove, Option<Eval>, SearchStats) {
+    if let Some(reply) = book.get(state) {
+        return (smallvec![reply], None, SearchStats::default());
+    }
+    let (next_moves, eval, stats) = find_next_move_with_stats(state, depth, parallel_until_depth);
+    (next_moves, Some(eval), stats)
+}
+
+/// Like `find_next_move`, but stops early once `cancel` is set, e.g. from
+/// another thread. Returns `None` if the search was cancelled.
+pub fn find_next_move_cancellable<P: Position>(
+    state: &P,
+    depth: u8,
+    parallel_until_depth: u8,
+    cancel: &AtomicBool,
+) -> Option<(NextMove<P::Move>, Eval)> {
+    let mut table = TranspositionTable::default();
+    let mut searcher = AlphaBeta::new(&mut table);
+    searcher.parallel_until_depth = parallel_until_depth;
+    searcher.cancel = Some(cancel);
+    search_root(state, depth, &mut searcher)
+}
+
+/// Serial search that keeps the searched positions in `table`.
+/// The table can be reused for the next move, positions that were
+/// searched with the same depth don't have to be searched again.
+pub fn find_next_move_with_table<P: Position>(
+    state: &P,
+    depth: u8,
+    table: &mut TranspositionTable<P>,
+) -> (NextMove<P::Move>, Eval) {
+    search_root(state, depth, &mut AlphaBeta::new(table)).unwrap()
+}
+
+/// Every legal move with its eval, best first. Moves with the same eval are
+/// in search order, for columns from the center outwards.
+pub fn rank_moves<P: Position>(
+    state: &P,
+    depth: u8,
+) -> SmallVec<[(P::Move, Eval); COLUMNS as usize]> {
+    let mut table = TranspositionTable::default();
+    let mut searcher = AlphaBeta::new(&mut table);
+    searcher.root_depth = depth;
+    // Unlike `search_root`, which only needs to know which moves are best,
+    // every move gets searched with the full window to get its exact eval
+    let mut ranking: SmallVec<[(P::Move, Eval); COLUMNS as usize]> = state
+        .legal_moves()
+        .into_iter()
+        .filter_map(|m| match state.apply(m) {
+            MoveResult::Impossible => None,
+            MoveResult::Victory => Some((m, Eval::ImmediateVictory)),
+            MoveResult::State(next) => {
+                Some((m, searcher.eval_move(&next, depth, -VICTORY, VICTORY)))
+            }
+        })
+        .collect();
+    ranking.sort_by_key(|&(_, eval)| core::cmp::Reverse(eval));
+    ranking
+}
+
+/// Expected line of play starting with the best move, with the eval of the
+/// position. Ends with the winning move if the search finds a forced win.
+pub fn find_principal_variation<P: Position>(state: &P, depth: u8) -> (Vec<P::Move>, Eval) {
+    let mut table = TranspositionTable::default();
+    let (_, eval) = find_next_move_with_table(state, depth, &mut table);
+
+    let mut line = Vec::new();
+    let mut state = *state;
+    // The positions along the line have already been searched with
+    // the respective depth, so the table already contains their results
+    for depth in (0..=depth).rev() {
+        let (next_moves, _) = find_next_move_with_table(&state, depth, &mut table);
+        let m = match next_moves.first() {
+            Some(&m) => m,
+            None => break,
+        };
+        line.push(m);
+        match state.apply(m) {
+            MoveResult::State(next) => state = next,
+            _ => break,
+        }
+    }
+    (line, eval)
+}
+
+/// Searches with increasing depth until the time budget is used up.
+/// Returns the result of the deepest completed search and its depth.
+#[cfg(feature = "std")]
+pub fn find_best_move_timed(state: &State, budget: Duration) -> (NextMove, Eval, u8) {
+    find_best_move_timed_with_clock(state, budget, &SystemClock::new())
+}
+
+/// Like `find_best_move_timed`, but takes the time from `clock`
+pub fn find_best_move_timed_with_clock(
+    state: &State,
+    budget: Duration,
+    clock: &dyn Clock,
+) -> (NextMove, Eval, u8) {
+    // Searching beyond a full board can't change the result
+    let config = SearchConfig::new()
+        .depth(state.empty_cells() as u8)
+        .parallel(false)
+        .time_budget(budget);
+    let mut completed = 0;
+    let (next_moves, eval, _) = search_with_clock(state, &config, clock, |depth, _, _| {
+        completed = depth;
+        ControlFlow::Continue(())
+    });
+    (next_moves, eval, completed)
+}
+
+/// Returns the best moves and their eval, or `None` if the search was aborted.
+fn search_root<P: Position>(
+    state: &P,
+    depth: u8,
+    searcher: &mut AlphaBeta<P>,
+) -> Option<(NextMove<P::Move>, Eval)> {
+    searcher.root_depth = depth;
+    searcher.stats.nodes += 1;
+
+    let mut move_states = match searcher.expand(state, None) {
+        Ok(move_states) => move_states,
+        Err((eval, m)) => {
+            searcher.stats.leaf_nodes += 1;
+            return Some((m.into_iter().collect(), eval));
+        }
+    };
+    // Without a win of its own, the side to move has to block the opponent's
+    // threats, moves that don't lose right away. If none block all of them,
+    // every move is searched and the eval shows the loss.
+    let blocking: SmallVec<[(P::Move, P); COLUMNS as usize]> = move_states
+        .iter()
+        .filter(|(_, next)| !next.has_immediate_win())
+        .copied()
+        .collect();
+    if !blocking.is_empty() {
+        move_states = blocking;
+    }
+    if depth == 0 {
+        searcher.stats.leaf_nodes += 1;
+    }
+
+    // On a symmetric board, a move and its mirror image are equally good, so
+    // only the moves up to the center (in search order) need to be searched
+    let searched: SmallVec<[(P::Move, P); COLUMNS as usize]> = if state.is_symmetric() {
+        let mut searched: SmallVec<[(P::Move, P); COLUMNS as usize]> = SmallVec::new();
+        for &(m, next) in &move_states {
+            if !searched
+                .iter()
+                .any(|&(other, _)| other == P::mirror_move(m))
+            {
+                searched.push((m, next));
+            }
+        }
+        searched
+    } else {
+        move_states.clone()
+    };
+
+    let searched_evals = if searcher.is_parallel_at(depth) {
+        searcher.eval_moves_parallel(&searched, depth, -VICTORY, VICTORY)
+    } else {
+        // Moves worse than the best one so far don't need an exact eval
+        let mut alpha = -VICTORY;
+        searched
+            .iter()
+            .map(|(m, next)| {
+                let eval = searcher.eval_move(next, depth, alpha, VICTORY);
+                alpha = alpha.max(eval.score());
+                (*m, eval)
+            })
+            .collect::<SmallVec<[(P::Move, Eval); COLUMNS as usize]>>()
+    };
+    if searcher.aborted {
+        return None;
+    }
+    let moves_evals: SmallVec<[(P::Move, Eval); COLUMNS as usize]> = move_states
+        .iter()
+        .map(|&(m, _)| {
+            let find = |m| searched_evals.iter().find(|&&(other, _)| other == m);
+            let &(_, eval) = find(m).or_else(|| find(P::mirror_move(m))).unwrap();
+            (m, eval)
+        })
+        .collect();
+
+    let eval = moves_evals
+        .iter()
+        .map(|&(_, eval)| eval)
+        .max_by_key(|eval| eval.search_key())
+        .unwrap();
+    let mut next_moves: NextMove<P::Move> = moves_evals
+        .iter()
+        .filter(|&&(_, move_eval)| move_eval == eval)
+        .map(|(m, _)| *m)
+        .collect();
+    // When losing anyway, make the opponent find the win: prefer the
+    // moves that leave them the fewest winning replies
+    if matches!(eval, Eval::AssuredLoss(_)) && next_moves.len() > 1 {
+        let replies: SmallVec<[usize; COLUMNS as usize]> = next_moves
+            .iter()
+            .map(|m| {
+                let (_, next) = move_states.iter().find(|(other, _)| other == m).unwrap();
+                searcher.winning_replies(next, depth)
+            })
+            .collect();
+        let fewest = *replies.iter().min().unwrap();
+        next_moves = next_moves
+            .into_iter()
+            .zip(replies)
+            .filter(|&(_, replies)| replies == fewest)
+            .map(|(m, _)| m)
+            .collect();
+    }
+    if searcher.aborted {
+        return None;
+    }
+    Some((next_moves, eval))
+}
+
+/// Depth-first search that skips subtrees which can't affect the result
+struct AlphaBeta<'a, P: Position> {
+    /// Disabled only to measure the effect of pruning
+    pruning: bool,
+    /// Abort the search once the clock reaches this
+    deadline: Option<(&'a dyn Clock, Duration)>,
+    /// Abort the search once this is set
+    cancel: Option<&'a AtomicBool>,
+    aborted: bool,
+    /// Depth of the current search, to tell how far a position is from its start
+    root_depth: u8,
+    /// Positions with more plies left than this search their moves in parallel
+    parallel_until_depth: u8,
+    /// Scores positions where the search ends
+    weights: EvalWeights,
+    stats: SearchStats,
+    table: &'a mut TranspositionTable<P>,
+}
+
+impl<'a, P: Position> AlphaBeta<'a, P> {
+    fn new(table: &'a mut TranspositionTable<P>) -> Self {
+        Self {
+            pruning: true,
+            deadline: None,
+            cancel: None,
+            aborted: false,
+            root_depth: 0,
+            parallel_until_depth: u8::MAX,
+            weights: EvalWeights::default(),
+            stats: SearchStats::default(),
+            table,
+        }
+    }
+
+    /// Searcher with the settings of `config` that apply to every depth
+    fn with_config(table: &'a mut TranspositionTable<P>, config: &SearchConfig) -> Self {
+        let mut searcher = Self::new(table);
+        searcher.parallel_until_depth = config.parallel_until_depth;
+        searcher.weights = config.weights;
+        searcher
+    }
+
+    /// Whether a position with `depth` plies left searches its moves in parallel
+    fn is_parallel_at(&self, depth: u8) -> bool {
+        cfg!(feature = "parallel") && depth > self.parallel_until_depth
+    }
+
+    /// Whether the deadline has passed or the search was cancelled
+    fn should_abort(&self) -> bool {
+        if let Some((clock, deadline)) = self.deadline {
+            if clock.now() >= deadline {
+                return true;
+            }
+        }
+        self.cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// The possible moves and resulting states, in search order with `hint`
+    /// first. If no search is needed, instead returns the eval and the move
+    /// leading to it.
+    #[allow(clippy::type_complexity)]
+    fn expand(
+        &self,
+        state: &P,
+        hint: Option<P::Move>,
+    ) -> Result<SmallVec<[(P::Move, P); COLUMNS as usize]>, (Eval, Option<P::Move>)> {
+        if state.is_terminal() {
+            return Err((Eval::Draw, None));
+        }
+        let mut move_states: SmallVec<[(P::Move, P); COLUMNS as usize]> = SmallVec::new();
+        for m in state.legal_moves() {
+            match state.apply(m) {
+                MoveResult::Victory => return Err((Eval::ImmediateVictory, Some(m))),
+                MoveResult::Impossible => (),
+                MoveResult::State(next) => move_states.push((m, next)),
+            }
+        }
+        if let Some(index) = move_states.iter().position(|(m, _)| Some(*m) == hint) {
+            move_states[..=index].rotate_right(1);
+        }
+        Ok(move_states)
+    }
+
+    /// Eval of the move leading to `next` for the player making it,
+    /// looking `depth` plies past `next`. Bounds as for `eval`.
+    fn eval_move(&mut self, next: &P, depth: u8, alpha: i32, beta: i32) -> Eval {
+        if depth > 0 {
+            self.eval(next, depth - 1, -beta, -alpha)
+        } else {
+            next.forced_eval()
+                .unwrap_or_else(|| Eval::Neutral(next.heuristic_score(&self.weights)))
+        }
+        .for_previous_player()
+    }
+
+    /// Number of replies to the move leading to `next` that win for the
+    /// opponent, looking `depth` plies past `next` like `eval_move`
+    fn winning_replies(&mut self, next: &P, depth: u8) -> usize {
+        next.legal_moves()
+            .into_iter()
+            .filter(|&m| match next.apply(m) {
+                MoveResult::Impossible => false,
+                MoveResult::Victory => true,
+                MoveResult::State(after) => matches!(
+                    self.eval_move(&after, depth.saturating_sub(1), -VICTORY, VICTORY),
+                    Eval::AssuredVictory(_)
+                ),
+            })
+            .count()
+    }
+
+    /// Eval of `state` for the side to move. If the score turns out to be
+    /// below `alpha` or above `beta`, the search may stop early and return
+    /// any eval that is also beyond that bound.
+    /// If the search gets aborted, the returned eval is meaningless.
+    fn eval(&mut self, state: &P, depth: u8, alpha: i32, beta: i32) -> Eval {
+        self.stats.nodes += 1;
+        self.stats.max_depth_reached = self.stats.max_depth_reached.max(self.root_depth - depth);
+        // Checking the time is comparatively slow
+        if self.stats.nodes.is_multiple_of(1024) && self.should_abort() {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return Eval::Neutral(0);
+        }
+
+        // The best move in the table is stored for the key, not for `state`
+        let (key, mirrored) = state.canonical();
+        let mirror = |m| if mirrored { P::mirror_move(m) } else { m };
+        let entry = self.table.get(&key).map(|entry| Entry {
+            best_move: entry.best_move.map(mirror),
+            ..entry
+        });
+        // Deeper entries would make the eval depend on what was searched
+        // before, they only suggest the move to search first
+        if let Some(entry) = entry.filter(|entry| entry.depth == depth) {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.eval.score() > beta,
+                Bound::Upper => entry.eval.score() < alpha,
+            };
+            if usable {
+                self.stats.tt_hits += 1;
+                return entry.eval;
+            }
+        }
+
+        let (eval, best_move) = self.eval_uncached(
+            state,
+            depth,
+            alpha,
+            beta,
+            entry.and_then(|entry| entry.best_move),
+        );
+        if self.aborted {
+            return eval;
+        }
+        let bound = if eval.score() > beta {
+            Bound::Lower
+        } else if eval.score() < alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(
+            key,
+            Entry {
+                depth,
+                eval,
+                bound,
+                best_move: best_move.map(mirror),
+            },
+        );
+        eval
+    }
+
+    /// Evals of all moves, searched in parallel with the same bounds, in the
+    /// order of `move_states` no matter which thread finishes first.
+    /// Each thread uses its own table with the capacity of the searcher's.
+    fn eval_moves_parallel(
+        &mut self,
+        move_states: &[(P::Move, P)],
+        depth: u8,
+        alpha: i32,
+        beta: i32,
+    ) -> SmallVec<[(P::Move, Eval); COLUMNS as usize]> {
+        let capacity = self.table.capacity();
+        #[cfg(feature = "parallel")]
+        let move_states = move_states.par_iter();
+        // Not reached, `is_parallel_at` is always false
+        #[cfg(not(feature = "parallel"))]
+        let move_states = move_states.iter();
+        let results: Vec<_> = move_states
+            .map(|(m, next)| {
+                let mut table = TranspositionTable::new(capacity);
+                let mut child_searcher = AlphaBeta {
+                    table: &mut table,
+                    stats: SearchStats::default(),
+                    // Branches that only start after an abort would otherwise
+                    // search up to 1024 nodes each
+                    aborted: self.should_abort(),
+                    ..*self
+                };
+                let eval = child_searcher.eval_move(next, depth, alpha, beta);
+                (*m, eval, child_searcher.stats, child_searcher.aborted)
+            })
+            .collect();
+        for (.., stats, aborted) in &results {
+            self.stats.merge(stats);
+            self.aborted |= aborted;
+        }
+        results.into_iter().map(|(m, eval, ..)| (m, eval)).collect()
+    }
+
+    /// Also returns the move that lead to the eval, which is searched
+    /// first on the next visit. `hint` is the previous best move.
+    fn eval_uncached(
+        &mut self,
+        state: &P,
+        depth: u8,
+        mut alpha: i32,
+        beta: i32,
+        hint: Option<P::Move>,
+    ) -> (Eval, Option<P::Move>) {
+        let move_states = match self.expand(state, hint) {
+            Ok(move_states) => move_states,
+            Err(result) => {
+                self.stats.leaf_nodes += 1;
+                return result;
+            }
+        };
+        if depth == 0 {
+            self.stats.leaf_nodes += 1;
+        }
+
+        let mut best: Option<(Eval, P::Move)> = None;
+        if self.is_parallel_at(depth) {
+            for (m, eval) in self.eval_moves_parallel(&move_states, depth, alpha, beta) {
+                if best.is_none_or(|(best, _)| eval.search_key() > best.search_key()) {
+                    best = Some((eval, m));
+                }
+            }
+            let (eval, m) = best.unwrap();
+            return (eval, Some(m));
+        }
+        for (m, next) in &move_states {
+            let eval = self.eval_move(next, depth, alpha, beta);
+            if best.is_none_or(|(best, _)| eval.search_key() > best.search_key()) {
+                best = Some((eval, *m));
+            }
+            if self.pruning && eval.score() > beta {
+                break;
+            }
+            alpha = alpha.max(eval.score());
+        }
+        let (eval, m) = best.unwrap();
+        (eval, Some(m))
+    }
+}
+
+/// Best moves, their eval and the number of visited nodes
+#[cfg(test)]
+fn search_counting(
+    state: &State,
+    depth: u8,
+    parallel_until_depth: u8,
+    pruning: bool,
+    table: &mut TranspositionTable,
+) -> (NextMove, Eval, u64) {
+    let mut searcher = AlphaBeta::new(table);
+    searcher.pruning = pruning;
+    searcher.parallel_until_depth = parallel_until_depth;
+    let (next_moves, eval) = search_root(state, depth, &mut searcher).unwrap();
+    (next_moves, eval, searcher.stats.nodes)
+}
+
+/// Plays the given columns starting from the empty board
+#[cfg(test)]
+fn play(columns: &[u8]) -> State {
+    State::from_moves(columns).unwrap()
+}
+
+#[test]
+fn test_move_generation_covers_all_columns() {
+    let mut table = TranspositionTable::new(0);
+    let mut searcher = AlphaBeta::new(&mut table);
+    // Not symmetric, so every column gets searched
+    search_root(&play(&[0]), 1, &mut searcher);
+    assert_eq!(searcher.stats.nodes, 1 + COLUMNS as u64);
+    let mut searcher = AlphaBeta::new(&mut table);
+    search_root(&State::empty(), 1, &mut searcher);
+    assert_eq!(searcher.stats.nodes, 1 + (COLUMNS as u64).div_ceil(2));
+    assert_eq!(MOVE_ORDER, [3, 2, 4, 1, 5, 0, 6]);
+}
+
+#[test]
+fn test_full_board_is_draw() {
+    let (moves, eval) = find_next_move(&crate::drawn_board(), 3, u8::MAX);
+    assert!(moves.is_empty());
+    assert!(matches!(eval, Eval::Draw));
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_forced_draw() {
+    use crate::{Cell::*, Player::*};
+
+    let state = State::from_board(
+        [
+            [Set(X), Empty,  Empty,  Set(X), Set(X), Set(O), Empty ],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+        ],
+        X,
+    );
+    let (moves, eval) = find_next_move(&state, 3, u8::MAX);
+    assert_eq!(moves.into_vec(), [Column(2), Column(1), Column(6)]);
+    assert!(matches!(eval, Eval::Draw));
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_draw_avoids_loss() {
+    use crate::{Cell::*, Player::*};
+
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+            [Set(X), Set(X), Set(O), Set(X), Set(X), Set(O), Set(X)],
+            [Set(O), Set(O), Set(X), Set(O), Set(O), Set(X), Set(O)],
+        ],
+        X,
+    );
+    let (moves, eval) = find_next_move(&state, 3, u8::MAX);
+    assert_eq!(moves.into_vec(), [Column(0)]);
+    assert!(matches!(eval, Eval::Draw));
+}
+
+#[test]
+fn test_pruning() {
+    for columns in [
+        &[3, 3, 2, 4, 3, 2, 4, 4, 2][..],
+        &[3, 3, 2, 4, 4, 2, 0, 1, 1, 2],
+        &[0, 1, 2, 3, 4, 5, 6, 6, 5, 4, 3, 2, 1, 0, 3, 3, 4],
+    ] {
+        let state = play(columns);
+        // Parallel branches don't prune each other, so only the root is parallel
+        for &parallel_until_depth in &[u8::MAX, 4] {
+            let table = &mut TranspositionTable::new(0);
+            let (moves, eval, nodes) =
+                search_counting(&state, 5, parallel_until_depth, true, table);
+            let (moves_full, eval_full, nodes_full) =
+                search_counting(&state, 5, parallel_until_depth, false, table);
+            assert_eq!(moves, moves_full);
+            assert_eq!(eval, eval_full);
+            assert!(nodes < nodes_full);
+        }
+    }
+}
+
+#[test]
+fn test_transposition_table() {
+    for columns in [
+        &[][..],
+        &[3, 3, 2, 4, 3, 2, 4, 4, 2],
+        &[3, 3, 2, 4, 4, 2, 0, 1, 1, 2],
+        &[0, 1, 2, 3, 4, 5, 6, 6, 5, 4, 3, 2, 1, 0, 3, 3, 4],
+    ] {
+        let state = play(columns);
+        let (moves, eval, nodes) =
+            search_counting(&state, 5, u8::MAX, true, &mut TranspositionTable::new(0));
+        let table = &mut TranspositionTable::default();
+        let (moves_cached, eval_cached, nodes_cached) =
+            search_counting(&state, 5, u8::MAX, true, table);
+        assert_eq!(moves, moves_cached);
+        assert_eq!(eval, eval_cached);
+        assert!(nodes_cached < nodes);
+        assert!(!table.is_empty());
+
+        // A reused table must give the same result
+        assert_eq!(find_next_move_with_table(&state, 5, table), (moves, eval));
+        table.clear();
+        assert!(table.is_empty());
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_timed_search() {
+    let state = play(&[3, 3, 2, 4]);
+
+    let (moves, _, _) = find_best_move_timed(&state, Duration::from_micros(1));
+    assert!(!moves.is_empty());
+    assert!(moves
+        .iter()
+        .all(|&column| state.try_move(column) != MoveResult::Impossible));
+
+    let (moves, _, depth) = find_best_move_timed(&state, Duration::from_millis(300));
+    assert!(!moves.is_empty());
+    assert!(depth >= 3);
+}
+
+#[test]
+fn test_timed_search_with_mock_clock() {
+    use crate::MockClock;
+
+    // Every completed depth takes 4 of the 10 seconds, so the third one
+    // uses the time up and no fourth one starts
+    let state = play(&[3, 3, 4, 2, 2]);
+    let config = SearchConfig::new()
+        .depth(20)
+        .parallel(false)
+        .time_budget(Duration::from_secs(10));
+    let clock = MockClock::new();
+    let mut depths = Vec::new();
+    let (next_moves, eval, stats) = search_with_clock(&state, &config, &clock, |depth, _, _| {
+        depths.push(depth);
+        clock.advance(Duration::from_secs(4));
+        ControlFlow::Continue(())
+    });
+    assert_eq!(depths, [0, 1, 2]);
+    assert_eq!((next_moves, eval), find_next_move(&state, 2, u8::MAX));
+    assert_eq!(stats.elapsed, Duration::from_secs(12));
+
+    // Without a budget, the clock only measures
+    let clock = MockClock::new();
+    let (_, _, stats) = search_with_clock(&state, &config.depth(3), &clock, |_, _, _| {
+        clock.advance(Duration::from_secs(1));
+        ControlFlow::Continue(())
+    });
+    assert_eq!(stats.elapsed, Duration::from_secs(4));
+    let config = SearchConfig {
+        time_budget: None,
+        ..config.depth(3)
+    };
+    let mut depths = Vec::new();
+    search_with_clock(&state, &config, &clock, |depth, _, _| {
+        depths.push(depth);
+        clock.advance(Duration::from_secs(100));
+        ControlFlow::Continue(())
+    });
+    assert_eq!(depths, [0, 1, 2, 3]);
+
+    // Without any time, only the depth 0 search is done
+    let (next_moves, _, depth) =
+        find_best_move_timed_with_clock(&State::empty(), Duration::ZERO, &MockClock::new());
+    assert!(!next_moves.is_empty());
+    assert_eq!(depth, 0);
+    // While the time stands still, until the board is full
+    let state = play(&[
+        6, 6, 5, 4, 2, 1, 2, 6, 2, 2, 5, 1, 5, 1, 2, 5, 6, 5, 4, 3, 5, 6, 1, 2, 6, 1, 1, 4,
+    ]);
+    let (next_moves, eval, depth) =
+        find_best_move_timed_with_clock(&state, Duration::from_secs(1), &MockClock::new());
+    assert_eq!(depth as usize, state.empty_cells());
+    assert_eq!((next_moves, eval), find_next_move(&state, depth, u8::MAX));
+}
+
+#[test]
+fn test_move_order() {
+    /// Searches the columns from left to right
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    struct LeftToRight(State);
+
+    impl Position for LeftToRight {
+        type Move = Column;
+
+        fn legal_moves(&self) -> SmallVec<[Column; COLUMNS as usize]> {
+            self.0.legal_moves().collect()
+        }
+
+        fn apply(&self, column: Column) -> MoveResult<Self> {
+            match self.0.try_move(column) {
+                MoveResult::Impossible => MoveResult::Impossible,
+                MoveResult::Victory => MoveResult::Victory,
+                MoveResult::State(next) => MoveResult::State(LeftToRight(next)),
+            }
+        }
+
+        fn is_terminal(&self) -> bool {
+            self.0.is_full()
+        }
+
+        fn heuristic_score(&self, weights: &EvalWeights) -> i32 {
+            self.0.heuristic_score(weights)
+        }
+
+        fn forced_eval(&self) -> Option<Eval> {
+            self.0.forced_eval()
+        }
+    }
+
+    let mut nodes = 0;
+    let mut nodes_left_to_right = 0;
+    for columns in [
+        &[3, 3, 2, 4, 3, 2, 4, 4, 2][..],
+        &[3, 3, 2, 4, 4, 2, 0, 1, 1, 2],
+        &[0, 1, 2, 3, 4, 5, 6, 6, 5, 4, 3, 2, 1, 0, 3, 3, 4],
+    ] {
+        let state = play(columns);
+        let mut table = TranspositionTable::new(0);
+        let mut searcher = AlphaBeta::new(&mut table);
+        let (moves, eval) = search_root(&state, 5, &mut searcher).unwrap();
+        nodes += searcher.stats.nodes;
+
+        let mut table = TranspositionTable::new(0);
+        let mut searcher = AlphaBeta::new(&mut table);
+        let (mut moves_left_to_right, eval_left_to_right) =
+            search_root(&LeftToRight(state), 5, &mut searcher).unwrap();
+        nodes_left_to_right += searcher.stats.nodes;
+
+        moves_left_to_right
+            .sort_by_key(|column| MOVE_ORDER.iter().position(|&c| Column(c) == *column));
+        assert_eq!(moves, moves_left_to_right);
+        assert_eq!(eval, eval_left_to_right);
+    }
+    assert!(nodes < nodes_left_to_right);
+}
+
+#[test]
+fn test_fastest_win() {
+    // Column 3 wins in 3 plies, column 4 in 5
+    let state = play(&[4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2]);
+    for &parallel_until_depth in &[u8::MAX, 3, 0] {
+        let (moves, eval) = find_next_move(&state, 5, parallel_until_depth);
+        assert_eq!(moves.into_vec(), [Column(3)]);
+        assert_eq!(eval, Eval::AssuredVictory(3));
+    }
+}
+
+#[test]
+fn test_slowest_loss() {
+    // Column 3 loses in 4 plies, all others in 2
+    let state = play(&[6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4]);
+    for &parallel_until_depth in &[u8::MAX, 3, 0] {
+        let (moves, eval) = find_next_move(&state, 5, parallel_until_depth);
+        assert_eq!(moves.into_vec(), [Column(3)]);
+        assert_eq!(eval, Eval::AssuredLoss(4));
+    }
+}
+
+#[test]
+fn test_parallel_search_is_reproducible() {
+    let states = [
+        play(&[3, 3, 2, 4]),
+        play(&[6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4]),
+        play(&[1, 5, 2, 6, 3, 6, 6]),
+    ];
+    for state in &states {
+        let expected = find_next_move(state, 4, u8::MAX);
+        // Searches running at the same time compete for rayon's threads
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| find_next_move(state, 4, 0)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        for result in results {
+            assert_eq!(result, expected);
+        }
+    }
+}
+
+#[test]
+fn test_serial_matches_parallel() {
+    // Without the `parallel` feature, both searches are serial
+    for seed in 0..20 {
+        let mut rng = oorandom::Rand32::new(seed);
+        let mut state = State::empty();
+        for _ in 0..rng.rand_range(0..16) {
+            let moves: Vec<_> = state.legal_moves().collect();
+            match state.try_move(moves[rng.rand_range(0..moves.len() as u32) as usize]) {
+                MoveResult::State(next) => state = next,
+                _ => break,
+            }
+        }
+        let serial = find_next_move(&state, 4, u8::MAX);
+        for &parallel_until_depth in &[DEFAULT_PARALLEL_UNTIL_DEPTH, 3, 0] {
+            assert_eq!(
+                find_next_move(&state, 4, parallel_until_depth),
+                serial,
+                "{:?}",
+                state
+            );
+        }
+    }
+}
+
+#[test]
+fn test_blocks_threat() {
+    // O threatens to complete the bottom row in column 4
+    let state = play(&[1, 0, 2, 6, 3]);
+    for depth in 0..5 {
+        for &parallel_until_depth in &[u8::MAX, 0] {
+            let (moves, eval) = find_next_move(&state, depth, parallel_until_depth);
+            assert_eq!(moves.into_vec(), [Column(4)]);
+            assert!(eval > Eval::AssuredLoss(2));
+        }
+        let (moves, _) = find_next_move(&BitBoard::from(state), depth, u8::MAX);
+        assert_eq!(moves.into_vec(), [Column(4)]);
+    }
+}
+
+#[test]
+fn test_losing_moves_leave_fewest_wins() {
+    // O wins in column 0 or 4. Blocking either leaves O a single winning
+    // reply, any other move leaves them both.
+    let state = play(&[1, 5, 2, 6, 3, 6, 6]);
+    for depth in 0..4 {
+        for &parallel_until_depth in &[u8::MAX, 0] {
+            let (moves, eval) = find_next_move(&state, depth, parallel_until_depth);
+            assert_eq!(moves.into_vec(), [Column(4), Column(0)]);
+            assert_eq!(eval, Eval::AssuredLoss(2));
+        }
+    }
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_fixture_results() {
+    use crate::{Cell::*, Player::*};
+
+    // Results of the search before it was restructured into negamax
+    let boards = [
+        (
+            [
+                [Empty,  Empty,  Empty,  Empty,  Empty, Set(X), Empty],
+                [Empty,  Empty,  Empty,  Empty,  Empty, Set(X), Empty],
+                [Empty,  Empty,  Empty,  Empty,  Empty, Set(O), Empty],
+                [Set(X), Set(O), Empty,  Empty,  Empty, Set(X), Empty],
+                [Set(X), Set(O), Set(O), Empty,  Empty, Set(X), Empty],
+                [Set(X), Set(O), Set(O), Set(O), Empty, Set(X), Empty],
+            ],
+            [(O, &[4][..], Eval::ImmediateVictory); 4],
+            [(X, &[0][..], Eval::ImmediateVictory); 4],
+        ),
+        (
+            [
+                [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+                [Empty,  Empty,  Empty,  Empty,  Set(O), Empty,  Empty],
+                [Empty,  Empty,  Empty,  Empty,  Set(O), Set(O), Empty],
+                [Empty,  Empty,  Set(X), Empty,  Set(O), Set(X), Set(X)],
+                [Empty,  Empty,  Set(O), Empty,  Set(X), Set(X), Set(O)],
+                [Set(X), Empty,  Set(O), Set(O), Set(X), Set(O), Set(X)],
+            ],
+            [(O, &[3][..], Eval::ImmediateVictory); 4],
+            [
+                // O's threats are found without searching, blocking the
+                // one in column 4 leaves O a single winning reply
+                (X, &[4][..], Eval::AssuredLoss(2)),
+                (X, &[4][..], Eval::AssuredLoss(2)),
+                (X, &[4][..], Eval::AssuredLoss(2)),
+                (X, &[4][..], Eval::AssuredLoss(2)),
+            ],
+        ),
+    ];
+    for (board, o_results, x_results) in &boards {
+        for results in &[o_results, x_results] {
+            for (&depth, (player, moves, eval)) in [0, 2, 4, 6].iter().zip(results.iter()) {
+                let state = State::from_board(*board, *player);
+                let expected = (moves.iter().copied().map(Column).collect(), *eval);
+                for &parallel_until_depth in &[u8::MAX, 3, 0] {
+                    assert_eq!(find_next_move(&state, depth, parallel_until_depth), expected);
+                    // Same generic search, different `Position`
+                    assert_eq!(
+                        find_next_move(&BitBoard::from(state), depth, parallel_until_depth),
+                        expected
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_principal_variation() {
+    /// Result of the last move in the line
+    fn replay(columns: &[u8], line: &[Column]) -> MoveResult {
+        let (last, line) = line.split_last().unwrap();
+        let line: Vec<u8> = line.iter().map(|&column| column.into()).collect();
+        play(&[columns, &line].concat()).try_move(*last)
+    }
+
+    let columns = [4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2];
+    let (line, eval) = find_principal_variation(&play(&columns), 5);
+    assert_eq!(eval, Eval::AssuredVictory(3));
+    assert_eq!(line.len(), 3);
+    assert_eq!(replay(&columns, &line), MoveResult::Victory);
+
+    let columns = [6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4];
+    let (line, eval) = find_principal_variation(&play(&columns), 5);
+    assert_eq!(eval, Eval::AssuredLoss(4));
+    assert_eq!(line.len(), 4);
+    assert_eq!(replay(&columns, &line), MoveResult::Victory);
+
+    let columns = [3, 3, 2, 4];
+    let (line, eval) = find_principal_variation(&play(&columns), 4);
+    assert!(matches!(eval, Eval::Neutral(_)));
+    assert_eq!(line.len(), 5);
+    assert!(matches!(replay(&columns, &line), MoveResult::State(_)));
+}
+
+#[test]
+fn test_search_stats() {
+    let state = play(&[3, 3, 2, 4]);
+    for &parallel_until_depth in &[u8::MAX, 3, 0] {
+        let (moves, eval, stats) = find_next_move_with_stats(&state, 4, parallel_until_depth);
+        assert_eq!(
+            (moves, eval),
+            find_next_move(&state, 4, parallel_until_depth)
+        );
+        assert!(stats.nodes >= stats.leaf_nodes);
+        assert!(stats.leaf_nodes > 0);
+        assert_eq!(stats.max_depth_reached, 4);
+
+        let (_, _, deeper) = find_next_move_with_stats(&state, 5, parallel_until_depth);
+        assert!(deeper.nodes > stats.nodes);
+        assert_eq!(deeper.max_depth_reached, 5);
+    }
+}
+
+/// Nodes a serial search of the empty board to depth 8 visits. The search is
+/// deterministic, so this only changes with the search itself. After a change
+/// meant to make it search more or fewer nodes, replace it with the count
+/// `test_node_count` prints with `cargo test test_node_count -- --nocapture`.
+#[cfg(test)]
+const EMPTY_BOARD_NODES: u64 = 2573;
+
+/// Catches changes that break pruning or move ordering, without timing that
+/// depends on the machine
+#[test]
+fn test_node_count() {
+    let config = SearchConfig::new().depth(8).parallel(false);
+    let (_, _, stats) = search(&State::empty(), &config);
+    println!("{} nodes, {} expected", stats.nodes, EMPTY_BOARD_NODES);
+    assert!(
+        stats.nodes <= 2 * EMPTY_BOARD_NODES,
+        "searched {} nodes, more than twice the {} of EMPTY_BOARD_NODES",
+        stats.nodes,
+        EMPTY_BOARD_NODES
+    );
+}
+
+#[test]
+fn test_search_config() {
+    let config = SearchConfig::new()
+        .depth(5)
+        .parallel(false)
+        .table_capacity(1000);
+    assert_eq!(config.parallel_until_depth, u8::MAX);
+    assert_eq!(
+        config.parallel(true).parallel_until_depth,
+        DEFAULT_PARALLEL_UNTIL_DEPTH
+    );
+
+    // Same as the lower-level functions with the same settings
+    let state = play(&[3, 3, 4, 2, 2]);
+    let (next_moves, eval, stats) = search(&state, &config);
+    assert_eq!(
+        (next_moves.clone(), eval),
+        find_next_move(&state, 5, u8::MAX)
+    );
+    assert_eq!(
+        stats,
+        SearchStats {
+            elapsed: stats.elapsed,
+            ..find_next_move_with_stats(&state, 5, u8::MAX).2
+        }
+    );
+    let weights = EvalWeights {
+        center: 10,
+        ..EvalWeights::default()
+    };
+    let (next_moves, eval, _) = search(&state, &config.weights(weights));
+    assert_eq!(
+        (next_moves, eval),
+        find_next_move_with_weights(&state, 5, u8::MAX, &weights)
+    );
+
+    // Out of time right away, only the first shallow search completes
+    let timed = config.depth(20).time_budget(Duration::from_micros(1));
+    let (next_moves, _, stats) = search(&State::empty(), &timed);
+    assert!(!next_moves.is_empty());
+    assert!(stats.max_depth_reached < 20);
+    let timed = config.time_budget(Duration::from_secs(600));
+    let (next_moves, eval, _) = search(&state, &timed);
+    assert_eq!((next_moves, eval), find_next_move(&state, 5, u8::MAX));
+}
+
+#[test]
+fn test_evaluate_batch() {
+    let config = SearchConfig::new().depth(6);
+    // Every position of a game, the later ones first. Their searches fill the
+    // table with positions the earlier ones reach with less depth left.
+    let moves = [2, 1, 3, 3, 4, 3];
+    let mut positions: Vec<_> = (0..=moves.len())
+        .rev()
+        .map(|ply| State::from_moves(&moves[..ply]).unwrap())
+        .collect();
+    let mut table = TranspositionTable::new(config.table_capacity);
+    for state in &positions {
+        let eval = evaluate_in_table(state, &config.parallel(false), &mut table);
+        assert_eq!(eval, state.evaluate(config.depth), "{:?}", state);
+    }
+    // Finished games aren't searched
+    positions.push(State::from_moves(&[0, 1, 0, 1, 0, 1, 0]).unwrap());
+    positions.push(crate::drawn_board());
+    let evals = evaluate_batch(&positions, &config);
+    assert_eq!(evals.len(), positions.len());
+    for (state, &eval) in positions.iter().zip(&evals) {
+        assert_eq!(eval, state.evaluate(config.depth), "{:?}", state);
+    }
+    assert_eq!(evals[evals.len() - 2..], [Eval::AssuredLoss(0), Eval::Draw]);
+    assert!(evaluate_batch(&[], &config).is_empty());
+}
+
+#[test]
+fn test_search_progress() {
+    let state = play(&[3, 3, 4, 2, 2]);
+    let config = SearchConfig::new().depth(5).parallel(false);
+    let mut steps = Vec::new();
+    let (next_moves, eval, _) = search_with_progress(&state, &config, |depth, best, eval| {
+        steps.push((depth, best.clone(), eval));
+        ControlFlow::Continue(())
+    });
+    let depths: Vec<_> = steps.iter().map(|&(depth, _, _)| depth).collect();
+    assert_eq!(depths, [0, 1, 2, 3, 4, 5]);
+    assert_eq!(steps.last(), Some(&(5, next_moves.clone(), eval)));
+    assert_eq!((next_moves, eval), find_next_move(&state, 5, u8::MAX));
+
+    // Stopping early keeps the result at that depth
+    let mut calls = 0;
+    let (next_moves, eval, _) = search_with_progress(&state, &config, |depth, _, _| {
+        calls += 1;
+        if depth == 2 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(calls, 3);
+    assert_eq!((next_moves, eval), find_next_move(&state, 2, u8::MAX));
+}
+
+#[test]
+fn test_cancel_search() {
+    use std::{sync::Arc, time::Instant};
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let cancel = Arc::clone(&cancel);
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let result = find_next_move_cancellable(
+                &State::empty(),
+                30,
+                DEFAULT_PARALLEL_UNTIL_DEPTH,
+                &cancel,
+            );
+            (result, start.elapsed())
+        })
+    };
+    std::thread::sleep(Duration::from_millis(50));
+    cancel.store(true, Ordering::Relaxed);
+    let (result, elapsed) = handle.join().unwrap();
+    assert_eq!(result, None);
+    assert!(elapsed < Duration::from_secs(5));
+
+    // An unset flag doesn't change the result
+    let state = play(&[3, 3, 2, 4]);
+    assert_eq!(
+        find_next_move_cancellable(&state, 4, u8::MAX, &AtomicBool::new(false)),
+        Some(find_next_move(&state, 4, u8::MAX))
+    );
+}
+
+#[test]
+fn test_bitboard_search() {
+    for columns in [
+        &[][..],
+        &[3, 3, 2, 4, 3, 2, 4, 4, 2],
+        &[4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2],
+        &[6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4],
+    ] {
+        let state = play(columns);
+        for &parallel_until_depth in &[u8::MAX, 3, 0] {
+            assert_eq!(
+                find_next_move_bitboard(&state, 5, parallel_until_depth),
+                find_next_move(&state, 5, parallel_until_depth)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_mirrored_positions_share_table_entries() {
+    for columns in [
+        &[3, 3, 2, 4, 3, 2, 4, 4, 2][..],
+        &[4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2],
+        &[6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4],
+    ] {
+        let state = play(columns);
+        let mirrored = state.mirrored();
+        assert_ne!(state, mirrored);
+        assert_eq!(mirrored.mirrored(), state);
+        assert_eq!(state.canonical().0, mirrored.canonical().0);
+        assert_ne!(state.canonical().1, mirrored.canonical().1);
+        let bitboard = BitBoard::from(state);
+        assert_eq!(bitboard.canonical().0, bitboard.mirrored().canonical().0);
+
+        let table = &mut TranspositionTable::default();
+        let (moves, eval) = find_next_move_with_table(&state, 5, table);
+        let entries = table.len();
+        // Everything the mirrored search needs is in the table already,
+        // with the best moves stored for the canonical position
+        let (mirrored_moves, mirrored_eval) = find_next_move_with_table(&mirrored, 5, table);
+        assert_eq!(table.len(), entries);
+        assert_eq!(mirrored_eval, eval);
+        let mut reflected: Vec<_> = moves
+            .iter()
+            .map(|&column| State::mirror_move(column))
+            .collect();
+        reflected.sort_unstable();
+        let mut mirrored_moves = mirrored_moves.into_vec();
+        mirrored_moves.sort_unstable();
+        assert_eq!(mirrored_moves, reflected);
+        assert_eq!(
+            find_next_move(&mirrored, 5, u8::MAX).1,
+            find_next_move(&state, 5, u8::MAX).1
+        );
+    }
+}
+
+#[test]
+fn test_search_is_mirror_symmetric() {
+    for seed in 0..40 {
+        // Random positions with at least one legal move left
+        let mut rng = oorandom::Rand32::new(seed);
+        let mut state = State::empty();
+        for _ in 0..rng.rand_range(0..20) {
+            let moves: Vec<_> = state.legal_moves().collect();
+            match state.try_move(moves[rng.rand_range(0..moves.len() as u32) as usize]) {
+                MoveResult::State(next) if !next.is_full() => state = next,
+                _ => break,
+            }
+        }
+
+        let (moves, eval) = find_next_move(&state, 4, u8::MAX);
+        let (mirrored_moves, mirrored_eval) = find_next_move(&state.mirrored(), 4, u8::MAX);
+        assert_eq!(mirrored_eval, eval, "{:?}", state);
+        let mut reflected: Vec<_> = moves
+            .iter()
+            .map(|&column| State::mirror_move(column))
+            .collect();
+        reflected.sort_unstable();
+        let mut mirrored_moves = mirrored_moves.into_vec();
+        mirrored_moves.sort_unstable();
+        assert_eq!(mirrored_moves, reflected, "{:?}", state);
+    }
+    assert_eq!(State::mirror_move(Column(0)), Column(COLUMNS - 1));
+    assert_eq!(State::mirror_move(Column(3)), Column(3));
+}
+
+#[test]
+fn test_symmetric_positions_search_half() {
+    // Symmetric, then not: X answers off-center
+    for columns in [&[][..], &[3, 3], &[3, 3, 1, 2, 5, 4], &[3, 2]] {
+        let state = play(columns);
+        let symmetric = columns != [3, 2];
+        assert_eq!(state.is_symmetric(), symmetric);
+        assert_eq!(BitBoard::from(state).is_symmetric(), symmetric);
+        let (next_moves, eval) = find_next_move(&state, 5, u8::MAX);
+        if symmetric {
+            for &m in &next_moves {
+                assert!(next_moves.contains(&State::mirror_move(m)));
+            }
+        }
+        // Ranking searches every move with the full window
+        let ranking = rank_moves(&state, 5);
+        assert_eq!(eval, ranking[0].1);
+        let mut best: Vec<_> = ranking
+            .iter()
+            .filter(|&&(_, move_eval)| move_eval == eval)
+            .map(|&(m, _)| m)
+            .collect();
+        best.sort_by_key(|&m| MOVE_ORDER.iter().position(|&c| Column(c) == m));
+        assert_eq!(next_moves.into_vec(), best);
+    }
+}
+
+#[test]
+fn test_rank_moves() {
+    // Only column 3 wins immediately
+    let state = play(&[0, 6, 1, 6, 2, 5]);
+    let ranking = rank_moves(&state, 4);
+    assert_eq!(ranking.len(), state.legal_moves().count());
+    assert_eq!(ranking[0], (Column(3), Eval::ImmediateVictory));
+    assert!(ranking[1..]
+        .iter()
+        .all(|&(_, eval)| eval < Eval::ImmediateVictory));
+
+    // Column 3 wins in 3 plies, column 4 in 5
+    let state = play(&[4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2]);
+    let ranking = rank_moves(&state, 5);
+    assert_eq!(ranking[0], (Column(3), Eval::AssuredVictory(3)));
+    assert_eq!(ranking[1], (Column(4), Eval::AssuredVictory(5)));
+    assert!(ranking.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+
+    // The best moves are the ones the search picks
+    let state = play(&[3, 3, 2, 4]);
+    let (moves, eval) = find_next_move(&state, 4, u8::MAX);
+    let ranking = rank_moves(&state, 4);
+    assert_eq!(ranking[0].1, eval);
+    let best: NextMove = ranking
+        .iter()
+        .take_while(|&&(_, move_eval)| move_eval == eval)
+        .map(|(column, _)| *column)
+        .collect();
+    assert_eq!(best, moves);
+
+    assert!(rank_moves(&crate::drawn_board(), 3).is_empty());
+}
+
+#[test]
+fn test_eval_order() {
+    let ascending = [
+        Eval::AssuredLoss(2),
+        Eval::AssuredLoss(6),
+        Eval::Draw,
+        Eval::Neutral(-VICTORY),
+        Eval::Neutral(-3),
+        Eval::Neutral(0),
+        Eval::Neutral(3),
+        Eval::Neutral(VICTORY / 2),
+        Eval::Neutral(VICTORY),
+        Eval::AssuredVictory(7),
+        Eval::AssuredVictory(3),
+        Eval::AssuredVictory(1),
+        Eval::ImmediateVictory,
+    ];
+    assert!(ascending.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(
+        Eval::AssuredVictory(3).cmp(&Eval::AssuredVictory(3)),
+        core::cmp::Ordering::Equal
+    );
+
+    // The search ranks a draw like a neutral score of 0, just below it
+    let search_ascending = [
+        Eval::AssuredLoss(2),
+        Eval::AssuredLoss(6),
+        Eval::Neutral(-VICTORY),
+        Eval::Neutral(-3),
+        Eval::Draw,
+        Eval::Neutral(0),
+        Eval::Neutral(3),
+        Eval::Neutral(VICTORY / 2),
+        Eval::Neutral(VICTORY),
+        Eval::AssuredVictory(7),
+        Eval::AssuredVictory(3),
+        Eval::AssuredVictory(1),
+        Eval::ImmediateVictory,
+    ];
+    assert!(search_ascending
+        .windows(2)
+        .all(|pair| pair[0].search_key() < pair[1].search_key()));
+    // The eval for the opponent is in reverse order. Both an immediate
+    // victory and a victory in one ply turn into a loss in two plies.
+    assert!(search_ascending[..search_ascending.len() - 1]
+        .windows(2)
+        .filter(|pair| !pair.contains(&Eval::Draw))
+        .all(|pair| pair[0].for_previous_player().search_key()
+            > pair[1].for_previous_player().search_key()));
+}
+
+#[test]
+fn test_eval_display() {
+    use alloc::string::ToString;
+
+    assert_eq!(Eval::ImmediateVictory.to_string(), "immediate win");
+    assert_eq!(Eval::AssuredVictory(3).to_string(), "winning in 3 plies");
+    assert_eq!(Eval::AssuredLoss(2).to_string(), "losing in 2 plies");
+    assert_eq!(Eval::Draw.to_string(), "drawn");
+    assert_eq!(Eval::Neutral(-6).to_string(), "unclear (score -6)");
+    // Debug stays as it was
+    assert_eq!(format!("{:?}", Eval::AssuredLoss(2)), "AssuredLoss(2)");
+
+    let state = play(&[0, 1, 0, 1, 0, 1]);
+    assert_eq!(state.try_move(Column(0)).to_string(), "move wins");
+    assert_eq!(state.try_move(Column(2)).to_string(), "game goes on");
+    assert_eq!(
+        play(&[0; 6]).try_move(Column(0)).to_string(),
+        "move can't be played"
+    );
+}
+
+#[test]
+fn test_terminal_value() {
+    // O has won, X would be to move
+    let won = State::from_moves(&[0, 1, 0, 1, 0, 1, 0]).unwrap();
+    assert!(won.is_terminal());
+    assert_eq!(won.terminal_value(), Some(Eval::AssuredLoss(0)));
+    let drawn = crate::drawn_board();
+    assert!(drawn.is_terminal());
+    assert_eq!(drawn.terminal_value(), Some(Eval::Draw));
+    for ongoing in [State::empty(), play(&[0, 1, 0, 1, 0, 1])] {
+        assert!(!ongoing.is_terminal());
+        assert_eq!(ongoing.terminal_value(), None);
+        assert!(!Position::is_terminal(&ongoing));
+    }
+    assert!(Position::is_terminal(&drawn));
+}
+
+#[test]
+fn test_evaluate() {
+    let mut state = play(&[0, 0, 1, 1, 2, 2]);
+    assert_eq!(state.evaluate(3), Eval::ImmediateVictory);
+    // O has won, X is to move
+    state.try_move_mut(3).unwrap();
+    assert_eq!(state.evaluate(3), Eval::AssuredLoss(0));
+    assert_eq!(
+        state.evaluate(3).for_previous_player(),
+        Eval::AssuredVictory(1)
+    );
+
+    assert_eq!(crate::drawn_board().evaluate(3), Eval::Draw);
+
+    let state = play(&[4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2]);
+    assert_eq!(state.evaluate(5), find_next_move(&state, 5, u8::MAX).1);
+}
+
+#[test]
+fn test_3d_search() {
+    use crate::state3d::play_3d;
+
+    // O completes the space diagonal
+    let state = play_3d(&[0, 5, 5, 10, 10, 15, 10, 15, 15, 3]);
+    for &parallel_until_depth in &[u8::MAX, 0] {
+        let (moves, eval) = find_next_move(&state, 2, parallel_until_depth);
+        assert_eq!(moves.into_vec(), [15]);
+        assert_eq!(eval, Eval::ImmediateVictory);
+    }
+
+    // X has to block the vertical line
+    let state = play_3d(&[0, 1, 0, 1, 0]);
+    let (moves, _) = find_next_move(&state, 2, u8::MAX);
+    assert_eq!(moves.into_vec(), [0]);
+}
+
+#[test]
+fn test_unique_winning_move() {
+    // Only column 3 wins right away, and nothing else wins later
+    let mate_in_one = play(&[
+        6, 6, 5, 4, 2, 1, 2, 6, 2, 2, 5, 1, 5, 1, 2, 5, 6, 5, 4, 3, 5, 6, 1, 2, 6, 1, 1, 4,
+    ]);
+    assert_eq!(mate_in_one.unique_winning_move(), Some(Column(3)));
+    // Only column 3 forces a win, in 3 plies
+    let state = play(&[
+        0, 1, 2, 0, 4, 4, 1, 0, 1, 6, 4, 1, 4, 3, 5, 6, 1, 2, 2, 5, 2, 1, 5, 4, 6, 5, 0, 2,
+    ]);
+    assert_eq!(state.unique_winning_move(), Some(Column(3)));
+
+    // Columns 2 and 6 both win right away
+    let state = play(&[
+        2, 3, 6, 6, 3, 1, 1, 4, 6, 0, 6, 1, 6, 3, 1, 0, 4, 1, 5, 4, 3, 4, 3, 1, 5, 4, 5, 5,
+    ]);
+    assert_eq!(state.unique_winning_move(), None);
+    // Column 4 wins right away, another move wins later
+    let state = play(&[
+        3, 2, 2, 3, 6, 1, 6, 5, 0, 2, 2, 1, 3, 1, 1, 3, 3, 6, 3, 2, 5, 1, 5, 4, 0, 5, 6, 1,
+    ]);
+    assert_eq!(state.unique_winning_move(), None);
+    // Lost, or already over
+    let state = play(&[6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4]);
+    assert_eq!(state.unique_winning_move(), None);
+    assert_eq!(crate::drawn_board().unique_winning_move(), None);
+}
+
+#[test]
+fn test_solve() {
+    let state = play(&[4, 2, 0, 2, 1, 4, 5, 0, 0, 4, 0, 0, 2, 3, 2]);
+    assert_eq!(state.solve(), Eval::AssuredVictory(3));
+    let state = play(&[6, 6, 2, 5, 5, 4, 2, 5, 4, 5, 6, 2, 2, 3, 0, 1, 5, 6, 4]);
+    assert_eq!(state.solve(), Eval::AssuredLoss(4));
+    assert_eq!(crate::drawn_board().solve(), Eval::Draw);
+    // Too deep for a shallow search to see
+    let state = play(&[
+        1, 5, 0, 4, 2, 3, 0, 0, 4, 3, 3, 4, 0, 4, 5, 5, 5, 0, 1, 3, 0, 2, 6, 6, 6, 1, 1, 6,
+    ]);
+    assert_eq!(find_next_move(&state, 4, u8::MAX).1, Eval::Neutral(0));
+    assert_eq!(state.solve(), Eval::AssuredLoss(14));
+}
@@ -0,0 +1,151 @@
+//! Choosing one of the moves ranked by the search, for varied play
+
+use alloc::vec::Vec;
+
+use crate::{Eval, COLUMNS};
+
+/// Picks a move from a ranking as returned by `rank_moves`. At temperature 0
+/// it's always the first of the best moves, i.e. the most central one.
+/// At higher temperatures, moves are sampled with weights falling off
+/// exponentially with how far their score is below the best one, the
+/// temperature being the score difference that makes a move `e` times less
+/// likely. Moves that lose by force are only picked at extreme temperatures.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    temperature: f32,
+    /// Factor of each column's weight
+    prior: Option<[f32; COLUMNS as usize]>,
+    rng: oorandom::Rand32,
+}
+
+impl Selector {
+    pub fn new(temperature: f32, seed: u64) -> Self {
+        Self {
+            temperature: temperature.max(0.),
+            prior: None,
+            rng: oorandom::Rand32::new(seed),
+        }
+    }
+
+    /// Multiplies the weight of each column by its entry in `prior`, e.g. to
+    /// reach unusual positions by favoring the edges. Only moves close to
+    /// the best one are affected: at temperature 0 the prior picks among the
+    /// best moves, and moves that lose by force are never picked unless all
+    /// do. Moves beyond the columns of the board keep their weight.
+    pub fn with_prior(self, prior: [f32; COLUMNS as usize]) -> Self {
+        Self {
+            prior: Some(prior),
+            ..self
+        }
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    /// One of the moves in `ranking`, which has to be sorted best first
+    /// and can't be empty
+    pub fn select<M: Copy + Into<u8>>(&mut self, ranking: &[(M, Eval)]) -> M {
+        let (best_move, best) = ranking[0];
+        let prior = match self.prior {
+            None if self.temperature == 0. => return best_move,
+            prior => prior,
+        };
+        let loses = |eval| matches!(eval, Eval::AssuredLoss(_));
+        let weights: Vec<f32> = ranking
+            .iter()
+            .map(|&(m, eval)| {
+                let weight = if self.temperature == 0. {
+                    (eval == best) as u8 as f32
+                } else {
+                    ((eval.score() - best.score()) as f32 / self.temperature).exp()
+                };
+                match prior {
+                    None => weight,
+                    Some(_) if loses(eval) && !loses(best) => 0.,
+                    Some(prior) => {
+                        let factor = prior.get(m.into() as usize).copied().unwrap_or(1.);
+                        weight * factor.max(0.)
+                    }
+                }
+            })
+            .collect();
+        let mut sample = self.rng.rand_float() * weights.iter().sum::<f32>();
+        for (&(m, _), weight) in ranking.iter().zip(weights) {
+            if sample < weight {
+                return m;
+            }
+            sample -= weight;
+        }
+        // Only reached through rounding errors
+        best_move
+    }
+}
+
+#[test]
+fn test_temperature() {
+    use crate::{rank_moves, State};
+
+    let state = State::from_moves(&[3, 3, 2, 4]).unwrap();
+    let ranking = rank_moves(&state, 4);
+    assert!(ranking.len() > 1);
+
+    let mut selector = Selector::new(0., 1);
+    for seed in 0..20 {
+        assert_eq!(selector.select(&ranking), ranking[0].0);
+        assert_eq!(Selector::new(0., seed).select(&ranking), ranking[0].0);
+    }
+
+    let mut selector = Selector::new(1000., 1);
+    let picked: Vec<_> = (0..100).map(|_| selector.select(&ranking)).collect();
+    assert!(picked.iter().any(|&m| m != ranking[0].0));
+    assert!(picked
+        .iter()
+        .all(|m| ranking.iter().any(|(other, _)| other == m)));
+
+    // Any move but blocking column 0 loses, which stays out of reach
+    let state = State::from_moves(&[1, 4, 2, 6, 3]).unwrap();
+    let ranking = rank_moves(&state, 2);
+    for _ in 0..100 {
+        assert_eq!(selector.select(&ranking), crate::Column(0));
+    }
+}
+
+#[test]
+fn test_prior() {
+    use crate::{rank_moves, Column, State};
+
+    let state = State::from_moves(&[3, 3, 2, 4]).unwrap();
+    let ranking = rank_moves(&state, 4);
+    let mut edge = [1.; COLUMNS as usize];
+    edge[0] = 100.;
+    let (mut with, mut without) = (0, 0);
+    for seed in 0..200 {
+        without += (Selector::new(5., seed).select(&ranking) == Column(0)) as usize;
+        with += (Selector::new(5., seed).with_prior(edge).select(&ranking) == Column(0)) as usize;
+    }
+    assert!(with > 2 * without.max(10), "{} {}", with, without);
+
+    // At temperature 0 only the best moves are candidates
+    let best = ranking[0].1;
+    for seed in 0..50 {
+        let picked = Selector::new(0., seed).with_prior(edge).select(&ranking);
+        assert!(ranking.contains(&(picked, best)));
+    }
+    // The prior can only pick among the best moves, here the central one
+    let mut center = [0.; COLUMNS as usize];
+    center[3] = 1.;
+    let ranking = [(Column(2), best), (Column(3), best), (Column(0), best)];
+    assert_eq!(
+        Selector::new(0., 1).with_prior(center).select(&ranking),
+        Column(3)
+    );
+
+    // Any move but blocking column 0 loses, however much the prior favors it
+    let state = State::from_moves(&[1, 4, 2, 6, 3]).unwrap();
+    let ranking = rank_moves(&state, 2);
+    let mut selector = Selector::new(1e9, 1).with_prior([0.01, 1., 1., 1., 1., 1., 1000.]);
+    for _ in 0..100 {
+        assert_eq!(selector.select(&ranking), Column(0));
+    }
+}
@@ -0,0 +1,63 @@
+//! Positions labeled with their eval, from games of the engine against
+//! itself, e.g. for experimenting with learned evaluations
+
+use alloc::vec::Vec;
+
+use crate::{find_next_move, table::HashMap, Eval, Game, State, DEFAULT_PARALLEL_UNTIL_DEPTH};
+
+/// Plays `n` games of the engine against itself from the empty board,
+/// searching `depth` plies for every move. Returns each position where a
+/// move was searched with the eval for the side to move, in the order they
+/// were first reached. Positions reached again, as found by their Zobrist
+/// hash, aren't repeated. Among equally good moves, the engine picks
+/// randomly with `seed`, so the same seed gives the same positions.
+pub fn generate_selfplay_games(n: usize, depth: u8, seed: u64) -> Vec<(State, Eval)> {
+    let mut rng = oorandom::Rand32::new(seed);
+    let mut positions = Vec::new();
+    let mut seen = HashMap::default();
+    for _ in 0..n {
+        let mut game = Game::new(State::empty());
+        while game.outcome().is_none() {
+            let state = *game.state();
+            let (next_moves, eval) = find_next_move(&state, depth, DEFAULT_PARALLEL_UNTIL_DEPTH);
+            if seen.insert(state.hash, ()).is_none() {
+                positions.push((state, eval));
+            }
+            let column = next_moves[rng.rand_range(0..next_moves.len() as u32) as usize];
+            game.play(column.into()).unwrap();
+        }
+    }
+    positions
+}
+
+#[test]
+fn test_selfplay() {
+    use crate::MoveResult;
+
+    assert!(generate_selfplay_games(0, 2, 1).is_empty());
+
+    // A single game is a sequence of moves
+    let game = generate_selfplay_games(1, 2, 1);
+    assert_eq!(game[0].0, State::empty());
+    for pair in game.windows(2) {
+        let (previous, next) = (pair[0].0, pair[1].0);
+        assert!(previous
+            .legal_moves()
+            .any(|column| previous.try_move(column) == MoveResult::State(next)));
+    }
+
+    let positions = generate_selfplay_games(5, 2, 1);
+    assert_eq!(generate_selfplay_games(5, 2, 1), positions);
+    assert!(positions.starts_with(&game));
+    assert!(positions.len() > game.len());
+    for (index, (state, eval)) in positions.iter().enumerate() {
+        assert!(positions[..index].iter().all(|(other, _)| other != state));
+        assert_eq!(*eval, crate::find_next_move(state, 2, u8::MAX).1);
+
+        // Any position without a winner, floating pieces or extra pieces
+        // of either player can be reached from the empty board. Parsing
+        // checks for the latter two.
+        assert_eq!(state.winner(), None);
+        assert_eq!(State::from_text(&state.to_text()), Ok(*state));
+    }
+}
@@ -0,0 +1,107 @@
+//! Serde support: a `State` is stored as its board, top row first, and the
+//! side to move, e.g. `{"board": [["empty", "o", ...], ...], "player": "x"}`.
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Cell, Player, State, COLUMNS, ROWS, WINNING_LENGTH};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CellRepr {
+    Empty,
+    O,
+    X,
+    Blocked,
+}
+
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Cell::Empty => CellRepr::Empty,
+            Cell::Set(Player::O) => CellRepr::O,
+            Cell::Set(Player::X) => CellRepr::X,
+            Cell::Blocked => CellRepr::Blocked,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cell {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match CellRepr::deserialize(deserializer)? {
+            CellRepr::Empty => Cell::Empty,
+            CellRepr::O => Cell::Set(Player::O),
+            CellRepr::X => Cell::Set(Player::X),
+            CellRepr::Blocked => Cell::Blocked,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StateRepr {
+    board: [[Cell; COLUMNS as usize]; ROWS as usize],
+    player: Player,
+    #[serde(default = "default_win_length")]
+    win_length: u8,
+}
+
+fn default_win_length() -> u8 {
+    WINNING_LENGTH
+}
+
+impl Serialize for State {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StateRepr {
+            board: self.board.0,
+            player: self.player,
+            win_length: self.win_length,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = StateRepr::deserialize(deserializer)?;
+        let state = State {
+            win_length: repr.win_length,
+            ..State::from_board(repr.board, repr.player)
+        };
+        state.validate().map_err(D::Error::custom)?;
+        Ok(state)
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let mut blocked = State::from_moves(&[3]).unwrap();
+    blocked.block_cell(3).unwrap();
+    for state in [
+        blocked,
+        State::empty(),
+        State::new(Player::X),
+        State::from_moves(&[3, 3, 2, 4, 3, 2, 4, 4, 2]).unwrap(),
+        State::with_win_length(Player::O, 5),
+        crate::drawn_board(),
+    ] {
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+    }
+
+    let json = serde_json::to_string(&State::from_moves(&[0, 1]).unwrap()).unwrap();
+    assert!(json.contains(r#"["o","x","empty","empty","empty","empty","empty"]"#));
+    assert!(json.contains(r#""player":"o""#));
+}
+
+#[test]
+fn test_invalid_position_rejected() {
+    let mut json = serde_json::to_value(State::empty()).unwrap();
+    json["board"][4][2] = "x".into();
+    let error = serde_json::from_value::<State>(json).unwrap_err();
+    assert!(error.to_string().contains("column 2, row 4"));
+
+    let mut json = serde_json::to_value(State::empty()).unwrap();
+    json["board"][5][2] = "o".into();
+    let error = serde_json::from_value::<State>(json).unwrap_err();
+    assert!(error.to_string().contains("side to move"));
+}
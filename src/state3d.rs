@@ -0,0 +1,213 @@
+//! 3D variant on a 4×4×4 grid: pieces drop down one of 16 vertical columns
+//! and lines can run along any axis, face diagonal or space diagonal.
+//!
+//! Column `x + 4 * y` holds the cells (x, y, z) with z counted from the bottom.
+//! Cell (x, y, z) is bit `x + 4 * y + 16 * z` of the bitmasks.
+
+use crate::{Cell, MoveResult, Player};
+
+/// Cells along each edge of the grid, also the number of pieces in a row needed to win
+pub const SIZE_3D: u8 = 4;
+/// Number of columns to drop pieces into
+pub const COLUMNS_3D: u8 = SIZE_3D * SIZE_3D;
+
+/// Bitmasks of all lines of `SIZE_3D` cells
+pub(crate) const LINES: [u64; 76] = {
+    let mut lines = [0; 76];
+    let mut count = 0;
+    let size = SIZE_3D as i8;
+    // Directions with the first non-zero step positive, so each line is found once
+    let mut direction = 0;
+    while direction < 27 {
+        let step = [direction % 3 - 1, direction / 3 % 3 - 1, direction / 9 - 1];
+        let first_nonzero = if step[0] != 0 {
+            step[0]
+        } else if step[1] != 0 {
+            step[1]
+        } else {
+            step[2]
+        };
+        if first_nonzero > 0 {
+            let mut start = 0;
+            while start < 64 {
+                let cell = [start % 4, start / 4 % 4, start / 16];
+                let mut line = 0;
+                let mut offset = 0;
+                while offset < size {
+                    let x = cell[0] + offset * step[0];
+                    let y = cell[1] + offset * step[1];
+                    let z = cell[2] + offset * step[2];
+                    if x < 0 || x >= size || y < 0 || y >= size || z < 0 || z >= size {
+                        line = 0;
+                        break;
+                    }
+                    line |= 1 << (x + size * y + size * size * z);
+                    offset += 1;
+                }
+                if line != 0 {
+                    lines[count] = line;
+                    count += 1;
+                }
+                start += 1;
+            }
+        }
+        direction += 1;
+    }
+    assert!(count == lines.len());
+    lines
+};
+
+/// Position in the 3D variant
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct State3D {
+    /// Pieces of each player, indexed by `Player as usize`
+    pub(crate) pieces: [u64; 2],
+    pub(crate) player: Player,
+}
+
+impl Default for State3D {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl State3D {
+    /// Empty grid with `starting_player` to move
+    pub fn new(starting_player: Player) -> Self {
+        Self {
+            pieces: [0; 2],
+            player: starting_player,
+        }
+    }
+
+    /// Empty grid with `Player::O` to move
+    pub fn empty() -> Self {
+        Self::new(Player::O)
+    }
+
+    pub fn current_player(&self) -> Player {
+        self.player
+    }
+
+    /// Cell at (x, y, z), z counted from the bottom
+    pub fn cell(&self, x: u8, y: u8, z: u8) -> Cell {
+        let bit = 1 << (x + SIZE_3D * y + COLUMNS_3D * z);
+        if self.pieces[Player::O as usize] & bit != 0 {
+            Cell::Set(Player::O)
+        } else if self.pieces[Player::X as usize] & bit != 0 {
+            Cell::Set(Player::X)
+        } else {
+            Cell::Empty
+        }
+    }
+
+    /// Number of pieces in `column`
+    pub fn height(&self, column: u8) -> u8 {
+        ((self.pieces[0] | self.pieces[1]) & column_mask(column)).count_ones() as u8
+    }
+
+    /// Columns that aren't full yet, in ascending order
+    pub fn legal_moves(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..COLUMNS_3D).filter(move |&column| self.height(column) < SIZE_3D)
+    }
+
+    /// Whether no more pieces can be placed
+    pub fn is_full(&self) -> bool {
+        self.pieces[0] | self.pieces[1] == u64::MAX
+    }
+
+    /// Player with `SIZE_3D` pieces in a row anywhere in the grid
+    pub fn winner(&self) -> Option<Player> {
+        [Player::O, Player::X].iter().copied().find(|&player| {
+            let pieces = self.pieces[player as usize];
+            LINES.iter().any(|&line| line & !pieces == 0)
+        })
+    }
+
+    pub fn try_move(&self, column: u8) -> MoveResult<Self> {
+        if column >= COLUMNS_3D {
+            return MoveResult::Impossible;
+        }
+        let height = self.height(column);
+        if height == SIZE_3D {
+            return MoveResult::Impossible;
+        }
+
+        let bit = 1 << (column + COLUMNS_3D * height);
+        let own = self.pieces[self.player as usize] | bit;
+        // Only lines through the new piece can have been completed
+        if LINES
+            .iter()
+            .any(|&line| line & bit != 0 && line & !own == 0)
+        {
+            return MoveResult::Victory;
+        }
+        let mut next = *self;
+        next.pieces[self.player as usize] = own;
+        next.player = self.player.other();
+        MoveResult::State(next)
+    }
+}
+
+/// Bits of all cells of `column`
+fn column_mask(column: u8) -> u64 {
+    0x0001_0001_0001_0001 << column
+}
+
+/// Plays `columns` from the empty grid, all but the last must not win
+#[cfg(test)]
+pub(crate) fn play_3d(columns: &[u8]) -> State3D {
+    let mut state = State3D::empty();
+    for &column in columns {
+        match state.try_move(column) {
+            MoveResult::State(next) => state = next,
+            result => panic!("column {}: {:?}", column, result),
+        }
+    }
+    state
+}
+
+#[test]
+fn test_lines() {
+    // 48 along the axes, 24 face diagonals, 4 space diagonals
+    let mut sorted = LINES.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    assert_eq!(sorted.len(), 76);
+    assert!(LINES.iter().all(|line| line.count_ones() == SIZE_3D as u32));
+    let corner = 1;
+    assert_eq!(LINES.iter().filter(|&&line| line & corner != 0).count(), 7);
+}
+
+#[test]
+fn test_vertical_win() {
+    let state = play_3d(&[0, 1, 0, 1, 0, 1]);
+    assert_eq!(state.height(0), 3);
+    assert_eq!(state.cell(0, 0, 2), Cell::Set(Player::O));
+    assert_eq!(state.cell(1, 0, 2), Cell::Set(Player::X));
+    assert_eq!(state.cell(0, 0, 3), Cell::Empty);
+    assert_eq!(state.try_move(0), MoveResult::Victory);
+    assert!(matches!(state.try_move(2), MoveResult::State(next) if next.winner().is_none()));
+}
+
+#[test]
+fn test_space_diagonal_win() {
+    // O at (0, 0, 0), (1, 1, 1) and (2, 2, 2), completed by (3, 3, 3)
+    let state = play_3d(&[0, 5, 5, 10, 10, 15, 10, 15, 15, 3]);
+    assert_eq!(state.height(15), 3);
+    assert_eq!(state.winner(), None);
+    assert_eq!(state.try_move(15), MoveResult::Victory);
+
+    let mut won = state;
+    won.pieces[Player::O as usize] |= 1 << 63;
+    assert_eq!(won.winner(), Some(Player::O));
+}
+
+#[test]
+fn test_full_columns() {
+    let state = play_3d(&[0, 0, 0, 0]);
+    assert_eq!(state.try_move(0), MoveResult::Impossible);
+    assert_eq!(state.try_move(COLUMNS_3D), MoveResult::Impossible);
+    assert_eq!(state.legal_moves().count(), COLUMNS_3D as usize - 1);
+    assert!(!state.is_full());
+}
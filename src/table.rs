@@ -0,0 +1,107 @@
+#[cfg(not(feature = "std"))]
+use core::hash::{BuildHasherDefault, Hasher};
+
+use crate::{Eval, Position, State};
+
+#[cfg(feature = "std")]
+pub(crate) type HashMap<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+pub(crate) type HashMap<K, V> = hashbrown::HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+
+/// FNV-1a, for hash maps without `std`'s randomly seeded hasher.
+/// The keys are positions, so there is no need to resist collision attacks.
+#[cfg(not(feature = "std"))]
+pub(crate) struct FnvHasher(u64);
+
+#[cfg(not(feature = "std"))]
+impl Default for FnvHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// How an eval stored in the table relates to the true eval
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Bound {
+    Exact,
+    /// The true eval scores at least as high
+    Lower,
+    /// The true eval scores at most as high
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Entry<M> {
+    /// Remaining search depth the eval was computed with
+    pub depth: u8,
+    pub eval: Eval,
+    pub bound: Bound,
+    /// Move that lead to the eval
+    pub best_move: Option<M>,
+}
+
+/// Cache of already searched positions, shared between the branches of a
+/// search. Positions can be reached through different move orders, so this
+/// avoids searching them again.
+#[derive(Clone, Debug)]
+pub struct TranspositionTable<P: Position = State> {
+    entries: HashMap<P, Entry<P::Move>>,
+    capacity: usize,
+}
+
+impl<P: Position> Default for TranspositionTable<P> {
+    fn default() -> Self {
+        Self::new(1 << 20)
+    }
+}
+
+impl<P: Position> TranspositionTable<P> {
+    /// Table holding at most `capacity` positions.
+    /// Once full, only existing entries get updated.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::default(),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget all positions, e.g. between moves to free memory
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    pub(crate) fn get(&self, state: &P) -> Option<Entry<P::Move>> {
+        self.entries.get(state).copied()
+    }
+
+    pub(crate) fn insert(&mut self, state: P, entry: Entry<P::Move>) {
+        if self.entries.len() < self.capacity || self.entries.contains_key(&state) {
+            self.entries.insert(state, entry);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Plain text format for positions, e.g. for bug reports:
+//! a header naming the side to move, followed by the board as printed by
+//! `State::print_board`.
+//!
+//! ```text
+//! x to move
+//! .......
+//! .......
+//! .......
+//! .......
+//! .......
+//! ...o...
+//! ```
+
+use alloc::{format, string::String};
+
+use crate::{Cell, InvalidPosition, Player, State, COLUMNS, ROWS};
+
+const HEADER_SUFFIX: &str = " to move";
+
+/// Why a text can't be parsed as a `State`. Rows and columns are numbered from 0,
+/// rows from the top.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParseError {
+    /// The first line isn't "o to move" or "x to move"
+    InvalidHeader,
+    /// The board doesn't have `ROWS` rows
+    WrongRowCount,
+    /// The row doesn't have `COLUMNS` cells
+    WrongRowLength(u8),
+    /// The character at (column, row) isn't one of `.`, `o`, `x` or `#`
+    InvalidCharacter(u8, u8),
+    /// The position can't arise in a game
+    InvalidPosition(InvalidPosition),
+    /// A column is neither a number nor a letter
+    InvalidColumn,
+    /// A column is past the right edge of the board
+    ColumnOutOfRange(u8),
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ParseError::InvalidHeader => {
+                write!(f, "first line must be \"o to move\" or \"x to move\"")
+            }
+            ParseError::WrongRowCount => write!(f, "board must have {} rows", ROWS),
+            ParseError::WrongRowLength(row) => {
+                write!(f, "row {} must have {} cells", row, COLUMNS)
+            }
+            ParseError::InvalidCharacter(column, row) => {
+                write!(f, "invalid cell in column {}, row {}", column, row)
+            }
+            ParseError::InvalidPosition(error) => write!(f, "{}", error),
+            ParseError::InvalidColumn => write!(
+                f,
+                "columns are 0 to {} or a to {}",
+                COLUMNS - 1,
+                (b'a' + COLUMNS - 1) as char
+            ),
+            ParseError::ColumnOutOfRange(_) => write!(f, "column must be below {}", COLUMNS),
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// Column entered by a player, either its number or a letter with "a" for
+/// column 0, ignoring surrounding whitespace and case
+pub fn parse_column(s: &str) -> Result<u8, ParseError> {
+    let s = s.trim();
+    let column = match s.as_bytes() {
+        [letter] if letter.is_ascii_alphabetic() => letter.to_ascii_lowercase() - b'a',
+        _ if !s.is_empty() && s.bytes().all(|byte| byte.is_ascii_digit()) => {
+            // Too large for a u8 is out of range as well
+            s.parse().unwrap_or(u8::MAX)
+        }
+        _ => return Err(ParseError::InvalidColumn),
+    };
+    if column < COLUMNS {
+        Ok(column)
+    } else {
+        Err(ParseError::ColumnOutOfRange(column))
+    }
+}
+
+impl State {
+    /// Position in the text format, parsed by `from_text`
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}{}\n{}",
+            self.player.symbol(),
+            HEADER_SUFFIX,
+            self.print_board()
+        )
+    }
+
+    /// Parses the output of `to_text`, with the default winning length
+    pub fn from_text(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text.lines();
+        let mut header = lines
+            .next()
+            .and_then(|line| line.strip_suffix(HEADER_SUFFIX))
+            .ok_or(ParseError::InvalidHeader)?
+            .chars();
+        let player = match (header.next().and_then(Player::from_char), header.next()) {
+            (Some(player), None) => player,
+            _ => return Err(ParseError::InvalidHeader),
+        };
+
+        let mut board = [[Cell::Empty; COLUMNS as usize]; ROWS as usize];
+        let mut rows = 0;
+        for (row, line) in lines.enumerate() {
+            if row >= ROWS as usize {
+                return Err(ParseError::WrongRowCount);
+            }
+            let row = row as u8;
+            if line.chars().count() != COLUMNS as usize {
+                return Err(ParseError::WrongRowLength(row));
+            }
+            for (column, c) in line.chars().enumerate() {
+                board[row as usize][column] =
+                    Cell::from_char(c).ok_or(ParseError::InvalidCharacter(column as u8, row))?;
+            }
+            rows += 1;
+        }
+        if rows != ROWS {
+            return Err(ParseError::WrongRowCount);
+        }
+
+        let state = State::from_board(board, player);
+        state.validate().map_err(ParseError::InvalidPosition)?;
+        Ok(state)
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let [first, second] = crate::winning_moves_fixtures();
+    for state in [
+        State::empty(),
+        State::new(Player::X),
+        State::from_moves(&[3, 3, 2, 4, 3, 2, 4, 4, 2]).unwrap(),
+        crate::drawn_board(),
+        first,
+    ] {
+        assert_eq!(State::from_text(&state.to_text()), Ok(state));
+    }
+    // O has one piece more and is to move
+    assert_eq!(
+        State::from_text(&second.to_text()),
+        Err(ParseError::InvalidPosition(
+            InvalidPosition::WrongSideToMove
+        ))
+    );
+
+    let text = "o to move\n\
+                .......\n\
+                .......\n\
+                .......\n\
+                .....x.\n\
+                .o...xo\n\
+                xo...xo\n";
+    assert_eq!(State::from_text(text).unwrap().to_text(), text);
+}
+
+#[test]
+fn test_parse_errors() {
+    let board = ".......\n.......\n.......\n.......\n.......\n...o...\n";
+    assert!(State::from_text(&format!("x to move\n{}", board)).is_ok());
+
+    assert_eq!(
+        State::from_text(&format!("y to move\n{}", board)),
+        Err(ParseError::InvalidHeader)
+    );
+    assert_eq!(State::from_text(""), Err(ParseError::InvalidHeader));
+    assert_eq!(
+        State::from_text(&format!("x to move\n{}.......\n", board)),
+        Err(ParseError::WrongRowCount)
+    );
+    assert_eq!(
+        State::from_text("x to move\n.......\n"),
+        Err(ParseError::WrongRowCount)
+    );
+    assert_eq!(
+        State::from_text(&format!(
+            "x to move\n{}",
+            board.replacen(".......", "........", 1)
+        )),
+        Err(ParseError::WrongRowLength(0))
+    );
+    assert_eq!(
+        State::from_text(&format!("x to move\n{}", board.replace('o', "O"))),
+        Err(ParseError::InvalidCharacter(3, 5))
+    );
+    assert_eq!(
+        State::from_text(&format!(
+            "x to move\n{}",
+            board.replacen(".......", "..x....", 1)
+        )),
+        Err(ParseError::InvalidPosition(InvalidPosition::FloatingPiece(
+            2, 0
+        )))
+    );
+    assert_eq!(
+        State::from_text(&format!("o to move\n{}", board)),
+        Err(ParseError::InvalidPosition(
+            InvalidPosition::WrongSideToMove
+        ))
+    );
+    assert_eq!(
+        State::from_text(&format!(
+            "x to move\n{}",
+            board.replacen("...o...", "..oo...", 1)
+        )),
+        Err(ParseError::InvalidPosition(
+            InvalidPosition::WrongSideToMove
+        ))
+    );
+}
+
+#[test]
+fn test_parse_column() {
+    use alloc::string::ToString;
+
+    for column in 0..COLUMNS {
+        assert_eq!(parse_column(&column.to_string()), Ok(column));
+        let letter = (b'a' + column) as char;
+        assert_eq!(parse_column(&letter.to_string()), Ok(column));
+        assert_eq!(
+            parse_column(&letter.to_ascii_uppercase().to_string()),
+            Ok(column)
+        );
+    }
+    assert_eq!(parse_column(" 3\n"), Ok(3));
+    assert_eq!(parse_column("06"), Ok(6));
+
+    assert_eq!(parse_column("7"), Err(ParseError::ColumnOutOfRange(7)));
+    assert_eq!(parse_column("h"), Err(ParseError::ColumnOutOfRange(7)));
+    assert_eq!(parse_column("Z"), Err(ParseError::ColumnOutOfRange(25)));
+    assert_eq!(
+        parse_column("300"),
+        Err(ParseError::ColumnOutOfRange(u8::MAX))
+    );
+    assert_eq!(
+        parse_column("9").unwrap_err().to_string(),
+        "column must be below 7"
+    );
+
+    for garbage in ["", "  ", "-1", "1.5", "ab", "3 4", "+3", "é", "?"] {
+        assert_eq!(
+            parse_column(garbage),
+            Err(ParseError::InvalidColumn),
+            "{}",
+            garbage
+        );
+    }
+    assert_eq!(
+        ParseError::InvalidColumn.to_string(),
+        "columns are 0 to 6 or a to g"
+    );
+}
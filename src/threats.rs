@@ -0,0 +1,205 @@
+//! Threats: empty cells that would complete a line for a player.
+//! Some combinations of them win by force no matter how the opponent
+//! replies, even where the winning move lies beyond the search depth.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{bitboard::BitBoard, Player, State, COLUMNS, ROWS};
+
+impl State {
+    /// Cells (column, row) where a piece of `player` would complete a line,
+    /// among the cells that can be played right away, by column
+    pub fn immediate_threats(&self, player: Player) -> Vec<(u8, u8)> {
+        self.legal_moves()
+            .map(u8::from)
+            .map(|column| (column, self.drop_row(column).unwrap()))
+            .filter(|&(column, row)| self.check_win_through(column, row, player))
+            .collect()
+    }
+
+    /// Player who wins by force within their next move:
+    /// - the side to move if they can complete a line right away
+    /// - otherwise the opponent if they have two threats that can be played
+    ///   right away, or one with another of their threats right above it.
+    ///   The side to move can only block one of them, or has to block the
+    ///   lower one, allowing the opponent to play the one above.
+    pub fn forced_win_threats(&self) -> Option<Player> {
+        let player = self.player;
+        let opponent = player.other();
+        let mut playable_threats = 0;
+        for column in self.legal_moves().map(u8::from) {
+            let row = self.drop_row(column).unwrap();
+            if self.check_win_through(column, row, player) {
+                return Some(player);
+            }
+            if self.check_win_through(column, row, opponent) {
+                // Rows are counted from the top
+                let above = row
+                    .checked_sub(1)
+                    .is_some_and(|above| self.check_win_through(column, above, opponent));
+                playable_threats += if above { 2 } else { 1 };
+            }
+        }
+        Some(opponent).filter(|_| playable_threats >= 2)
+    }
+
+    /// The board as by `Display`, with the `immediate_threats` of both
+    /// players marked: `o` where O would win, `x` where X would and `*`
+    /// where either would
+    pub fn render_with_threats(&self) -> String {
+        let o = self.immediate_threats(Player::O);
+        let x = self.immediate_threats(Player::X);
+        self.board.render_with(|column, row| {
+            let cell = (column, row);
+            match (o.contains(&cell), x.contains(&cell)) {
+                (true, true) => "*",
+                (true, false) => "o",
+                (false, true) => "x",
+                (false, false) => self[cell].glyph(),
+            }
+        })
+    }
+}
+
+impl BitBoard {
+    /// Same as `State::forced_win_threats`
+    pub(crate) fn forced_win_threats(&self) -> Option<Player> {
+        let player = self.player;
+        let opponent = player.other();
+        let mut playable_threats = 0;
+        for column in 0..COLUMNS {
+            let height = self.height(column);
+            if height == ROWS {
+                continue;
+            }
+            if self.completes_line(player, Self::bit(column, height)) {
+                return Some(player);
+            }
+            if self.completes_line(opponent, Self::bit(column, height)) {
+                let above = height + 1 < ROWS
+                    && self.completes_line(opponent, Self::bit(column, height + 1));
+                playable_threats += if above { 2 } else { 1 };
+            }
+        }
+        Some(opponent).filter(|_| playable_threats >= 2)
+    }
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_double_threat() {
+    use crate::{Cell::*, Player::*};
+
+    // X can't block both ends of O's open three
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Set(X), Set(X), Empty,  Empty,  Set(O)],
+            [Empty,  Set(O), Set(O), Set(O), Empty,  Empty,  Set(X)],
+        ],
+        X,
+    );
+    assert_eq!(state.forced_win_threats(), Some(O));
+    assert_eq!(BitBoard::from(state).forced_win_threats(), Some(O));
+    assert_eq!(state.immediate_threats(O), [(0, 5), (4, 5)]);
+    assert_eq!(state.immediate_threats(X), []);
+
+    // O's threats in column 3 are stacked: X has to block the lower one,
+    // then O plays on top of it
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(X), Set(X), Set(X), Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Set(O), Set(O), Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Set(O), Set(O), Empty,  Empty,  Set(X), Set(X)],
+        ],
+        X,
+    );
+    assert_eq!(state.forced_win_threats(), Some(O));
+    assert_eq!(BitBoard::from(state).forced_win_threats(), Some(O));
+    // The upper one can't be played yet
+    assert_eq!(state.immediate_threats(O), [(3, 5)]);
+    assert_eq!(state.immediate_threats(X), []);
+
+    // Winning right away beats the opponent's threats
+    let state = State::from_board(state.board.0, O);
+    assert_eq!(state.forced_win_threats(), Some(O));
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_single_threat() {
+    use crate::{Cell::*, Player::*};
+
+    // X can block the only threat
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Set(X), Empty,  Empty,  Empty ],
+            [Set(X), Set(O), Set(O), Set(O), Empty,  Empty,  Empty ],
+        ],
+        X,
+    );
+    assert_eq!(state.forced_win_threats(), None);
+    assert_eq!(BitBoard::from(state).forced_win_threats(), None);
+    assert_eq!(state.immediate_threats(O), [(4, 5)]);
+
+    // O's threat in column 3 isn't playable yet, and the cell below it
+    // isn't a threat
+    let state = State::from_board(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Set(O), Set(O), Empty,  Empty,  Empty,  Empty ],
+            [Set(X), Set(O), Set(X), Empty,  Empty,  Empty,  Empty ],
+            [Set(O), Set(X), Set(O), Set(X), Set(X), Empty,  Empty ],
+        ],
+        X,
+    );
+    assert_eq!(state.forced_win_threats(), None);
+    assert_eq!(BitBoard::from(state).forced_win_threats(), None);
+    assert_eq!(state.immediate_threats(O), []);
+}
+
+#[rustfmt::skip]
+#[test]
+fn test_render_with_threats() {
+    use crate::{Cell::*, Player::*};
+
+    // O threatens to complete column 0 and the bottom row, X the bottom row
+    let state = State::from_grid(
+        [
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Empty,  Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Empty],
+            [Set(O), Empty,  Empty,  Empty,  Empty,  Empty,  Set(X)],
+            [Set(O), Set(O), Set(O), Empty,  Set(X), Set(X), Set(X)],
+        ],
+        X,
+    ).unwrap();
+    assert_eq!(
+        state.render_with_threats(),
+        "0 1 2 3 4 5 6\n\
+         · · · · · · ·\n\
+         · · · · · · ·\n\
+         o · · · · · ·\n\
+         O · · · · · ·\n\
+         O · · · · · X\n\
+         O O O * X X X\n"
+    );
+
+    let state = State::from_moves(&[3, 3, 2]).unwrap();
+    assert_eq!(state.render_with_threats(), state.to_string());
+    let state = State::from_moves(&[3, 3, 2, 2, 4]).unwrap();
+    assert_eq!(state.render_with_threats().matches('o').count(), 2);
+}
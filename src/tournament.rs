@@ -0,0 +1,119 @@
+//! Engines with different settings playing each other, to measure how much
+//! e.g. the search depth matters
+
+use alloc::vec::Vec;
+
+use crate::{search, Game, Outcome, Player, SearchConfig, State};
+
+/// Games between two engines of a tournament, counted for the first one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PairingResult {
+    /// Index of the first engine's config
+    pub first: usize,
+    /// Index of the second engine's config
+    pub second: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TournamentResult {
+    /// Every pair of engines once, ordered by their indices
+    pub pairings: Vec<PairingResult>,
+}
+
+impl TournamentResult {
+    /// Score of the engine with config `index` over all its games:
+    /// 1 for a win and 0.5 for a draw
+    pub fn points(&self, index: usize) -> f32 {
+        self.pairings
+            .iter()
+            .map(|pairing| {
+                let (wins, draws) = if pairing.first == index {
+                    (pairing.wins, pairing.draws)
+                } else if pairing.second == index {
+                    (pairing.losses, pairing.draws)
+                } else {
+                    (0, 0)
+                };
+                wins as f32 + draws as f32 / 2.
+            })
+            .sum()
+    }
+}
+
+/// Round robin: every pair of `configs` plays `games` games from the empty
+/// board, with the first engine moving first in the even ones. Among
+/// equally good moves, the engines pick randomly with `seed`, so the same
+/// seed gives the same result.
+pub fn run_tournament(configs: &[SearchConfig], games: usize, seed: u64) -> TournamentResult {
+    let mut rng = oorandom::Rand32::new(seed);
+    let mut pairings = Vec::new();
+    for first in 0..configs.len() {
+        for second in first + 1..configs.len() {
+            let mut pairing = PairingResult {
+                first,
+                second,
+                ..PairingResult::default()
+            };
+            for game in 0..games {
+                let (o, x) = if game % 2 == 0 {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                let winner = match play_game(&configs[o], &configs[x], &mut rng) {
+                    Outcome::Win(Player::O) => Some(o),
+                    Outcome::Win(Player::X) => Some(x),
+                    Outcome::Draw => None,
+                };
+                match winner {
+                    Some(winner) if winner == first => pairing.wins += 1,
+                    Some(_) => pairing.losses += 1,
+                    None => pairing.draws += 1,
+                }
+            }
+            pairings.push(pairing);
+        }
+    }
+    TournamentResult { pairings }
+}
+
+/// Plays a game between the engines playing O and X
+fn play_game(o: &SearchConfig, x: &SearchConfig, rng: &mut oorandom::Rand32) -> Outcome {
+    let mut game = Game::new(State::empty());
+    loop {
+        if let Some(outcome) = game.outcome() {
+            return outcome;
+        }
+        let state = *game.state();
+        let config = match state.current_player() {
+            Player::O => o,
+            Player::X => x,
+        };
+        let (next_moves, _, _) = search(&state, config);
+        let column = next_moves[rng.rand_range(0..next_moves.len() as u32) as usize];
+        game.play(column.into()).unwrap();
+    }
+}
+
+#[test]
+fn test_deeper_search_is_stronger() {
+    let configs = [SearchConfig::new().depth(0), SearchConfig::new().depth(4)];
+    let result = run_tournament(&configs, 4, 1);
+    assert_eq!(result, run_tournament(&configs, 4, 1));
+    assert_eq!(result.pairings.len(), 1);
+    let pairing = result.pairings[0];
+    assert_eq!((pairing.first, pairing.second), (0, 1));
+    assert_eq!(pairing.wins + pairing.draws + pairing.losses, 4);
+    assert!(pairing.losses >= pairing.wins);
+    assert!(result.points(1) >= result.points(0));
+    assert_eq!(result.points(0) + result.points(1), 4.);
+
+    // Every pair plays once
+    let result = run_tournament(&[configs[0]; 4], 2, 1);
+    assert_eq!(result.pairings.len(), 6);
+    assert_eq!((0..4).map(|index| result.points(index)).sum::<f32>(), 12.);
+    assert!(run_tournament(&configs[..1], 2, 1).pairings.is_empty());
+}
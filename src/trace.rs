@@ -0,0 +1,140 @@
+//! Explanations of the move the search chooses, for debugging the engine
+
+use smallvec::SmallVec;
+
+use crate::{
+    find_next_move, rank_moves, Column, Eval, EvalWeights, MoveResult, Position, COLUMNS,
+    DEFAULT_PARALLEL_UNTIL_DEPTH,
+};
+
+/// Why the search chooses the moves it does, from `trace_search`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalTrace<M = Column> {
+    /// Eval of the position, i.e. of the best moves
+    pub eval: Eval,
+    /// Every legal move, best first as by `rank_moves`
+    pub moves: SmallVec<[MoveTrace<M>; COLUMNS as usize]>,
+}
+
+/// What the search found out about a single move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveTrace<M = Column> {
+    pub m: M,
+    pub eval: Eval,
+    /// One of the moves the search picks
+    pub selected: bool,
+    /// Picked because it wins by force
+    pub forced_win: bool,
+    /// Skipped by the search because it lets the opponent win with their
+    /// next move while another move doesn't
+    pub pruned: bool,
+    /// Plies until the game is decided, if the search found a forced result
+    pub distance: Option<u8>,
+    /// Heuristic score of the position after the move, for the side making
+    /// it. `None` if the move wins.
+    pub heuristic: Option<i32>,
+}
+
+impl<M: Copy + Eq> EvalTrace<M> {
+    /// The moves the search picks, in the order of the ranking
+    pub fn selected(&self) -> impl Iterator<Item = M> + '_ {
+        self.moves
+            .iter()
+            .filter(|trace| trace.selected)
+            .map(|trace| trace.m)
+    }
+}
+
+/// Searches `depth` plies ahead like `find_next_move`, but explains the
+/// result move by move. Ranks every move as well, so it takes longer.
+pub fn trace_search<P: Position>(state: &P, depth: u8) -> EvalTrace<P::Move> {
+    let (selected, eval) = find_next_move(state, depth, DEFAULT_PARALLEL_UNTIL_DEPTH);
+    let weights = EvalWeights::default();
+    let ranking = rank_moves(state, depth);
+    let gives_win = |result: &MoveResult<P>| match result {
+        MoveResult::State(next) => next.has_immediate_win(),
+        MoveResult::Victory | MoveResult::Impossible => false,
+    };
+    // As in the search, moves letting the opponent win right away are only
+    // considered if every move does
+    let pruning = !ranking.iter().all(|&(m, _)| gives_win(&state.apply(m)));
+    let moves = ranking
+        .iter()
+        .map(|&(m, eval)| {
+            let result = state.apply(m);
+            let selected = selected.contains(&m);
+            MoveTrace {
+                m,
+                eval,
+                selected,
+                forced_win: selected
+                    && matches!(eval, Eval::ImmediateVictory | Eval::AssuredVictory(_)),
+                pruned: pruning && gives_win(&result),
+                distance: match eval {
+                    Eval::ImmediateVictory => Some(1),
+                    Eval::AssuredVictory(plies) | Eval::AssuredLoss(plies) => Some(plies),
+                    Eval::Draw | Eval::Neutral(_) => None,
+                },
+                heuristic: match result {
+                    MoveResult::State(next) => Some(-next.heuristic_score(&weights)),
+                    MoveResult::Victory | MoveResult::Impossible => None,
+                },
+            }
+        })
+        .collect();
+    EvalTrace { eval, moves }
+}
+
+#[test]
+fn test_trace_search() {
+    use crate::State;
+
+    // Only column 3 wins, right away
+    let state = State::from_moves(&[
+        6, 6, 5, 4, 2, 1, 2, 6, 2, 2, 5, 1, 5, 1, 2, 5, 6, 5, 4, 3, 5, 6, 1, 2, 6, 1, 1, 4,
+    ])
+    .unwrap();
+    let trace = trace_search(&state, 4);
+    assert_eq!(trace.eval, Eval::ImmediateVictory);
+    assert_eq!(trace.moves.len(), state.legal_moves().count());
+    let wins: SmallVec<[Column; COLUMNS as usize]> = trace
+        .moves
+        .iter()
+        .filter(|trace| trace.forced_win)
+        .map(|trace| trace.m)
+        .collect();
+    assert_eq!(wins.as_slice(), [Column(3)]);
+    assert!(trace.selected().eq([Column(3)]));
+    assert_eq!(trace.moves[0].distance, Some(1));
+    assert_eq!(trace.moves[0].heuristic, None);
+    assert!(trace.moves[1..]
+        .iter()
+        .all(|trace| trace.heuristic.is_some() && !trace.selected));
+
+    // Any move but blocking column 0 lets O win right away
+    let state = State::from_moves(&[1, 4, 2, 6, 3]).unwrap();
+    let trace = trace_search(&state, 4);
+    assert!(trace.selected().eq([Column(0)]));
+    for trace in &trace.moves {
+        assert_eq!(trace.pruned, trace.m != Column(0));
+        assert!(!trace.forced_win);
+        if trace.pruned {
+            assert_eq!(trace.eval, Eval::AssuredLoss(2));
+            assert_eq!(trace.distance, Some(2));
+        }
+    }
+
+    // O threatens both ends of the bottom row after playing column 2
+    let state = State::from_moves(&[3, 0, 3, 0, 4, 6, 4, 6]).unwrap();
+    let trace = trace_search(&state, 4);
+    assert_eq!(trace.eval, Eval::AssuredVictory(3));
+    assert!(trace.selected().eq([Column(2)]));
+    assert!(trace.moves[0].forced_win);
+    // Then every move of X lets O win, so none is pruned
+    let state = State::from_moves(&[3, 0, 3, 0, 4, 6, 4, 6, 2]).unwrap();
+    let trace = trace_search(&state, 4);
+    assert!(trace
+        .moves
+        .iter()
+        .all(|trace| !trace.pruned && !trace.forced_win));
+}
@@ -0,0 +1,96 @@
+//! JavaScript bindings for browser frontends.
+//! Build with the default features off, the threads and clock they use
+//! aren't available in the browser:
+//!
+//! ```text
+//! cargo build --target wasm32-unknown-unknown --no-default-features --features wasm
+//! ```
+
+use alloc::string::{String, ToString};
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{find_next_move, Game, Player, State};
+
+/// Game in progress, starting from the empty board with O to move
+#[wasm_bindgen]
+pub struct WasmGame {
+    game: Game,
+}
+
+impl Default for WasmGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl WasmGame {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            game: Game::new(State::empty()),
+        }
+    }
+
+    /// Drops a piece into `column` for the side to move.
+    /// Throws the reason if the move can't be played.
+    pub fn play(&mut self, column: u8) -> Result<(), String> {
+        self.game
+            .play(column)
+            .map(|_| ())
+            .map_err(|error| error.to_string())
+    }
+
+    /// Column the engine would play, searching `depth` plies ahead, or
+    /// `undefined` if the game is over
+    pub fn best_move(&self, depth: u8) -> Option<u8> {
+        let state = self.game.state();
+        if state.is_terminal() {
+            return None;
+        }
+        // Serial, there are no threads to search on
+        find_next_move(state, depth, u8::MAX)
+            .0
+            .first()
+            .map(|&column| column.into())
+    }
+
+    /// Board as printed by `State::print_board`
+    pub fn board_string(&self) -> String {
+        self.game.state().print_board()
+    }
+
+    /// `"O"` or `"X"` once a player has won, otherwise `undefined`
+    pub fn winner(&self) -> Option<String> {
+        self.game.winner().map(|player| {
+            match player {
+                Player::O => "O",
+                Player::X => "X",
+            }
+            .into()
+        })
+    }
+}
+
+#[test]
+fn test_wasm_game() {
+    let mut game = WasmGame::new();
+    for &column in &[3, 0, 3, 0, 3, 0] {
+        game.play(column).unwrap();
+    }
+    assert_eq!(game.play(7), Err("column must be below 7".into()));
+    assert_eq!(
+        game.board_string(),
+        State::from_moves(&[3, 0, 3, 0, 3, 0])
+            .unwrap()
+            .print_board()
+    );
+    assert_eq!(game.winner(), None);
+    assert_eq!(game.best_move(4), Some(3));
+
+    game.play(3).unwrap();
+    assert_eq!(game.winner(), Some("O".into()));
+    assert_eq!(game.best_move(4), None);
+    assert!(game.play(1).is_err());
+}
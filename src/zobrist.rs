@@ -0,0 +1,68 @@
+//! Random keys for Zobrist hashing: the hash of a position is the XOR of the
+//! keys of all its pieces, blocked cells and of the side to move, so it can
+//! be updated incrementally when a piece is placed.
+
+use crate::{Player, COLUMNS, ROWS};
+
+/// Key for each player's piece on each cell, indexed by player, row and column
+const PIECES: [[[u64; COLUMNS as usize]; ROWS as usize]; 2] = {
+    let mut keys = [[[0; COLUMNS as usize]; ROWS as usize]; 2];
+    let mut seed: u64 = 0x0c0f_fee0_c0ff_ee00;
+    let mut player = 0;
+    while player < 2 {
+        let mut row = 0;
+        while row < ROWS as usize {
+            let mut column = 0;
+            while column < COLUMNS as usize {
+                seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                keys[player][row][column] = mix(seed);
+                column += 1;
+            }
+            row += 1;
+        }
+        player += 1;
+    }
+    keys
+};
+
+/// Key for a blocked cell, indexed by row and column
+const BLOCKED: [[u64; COLUMNS as usize]; ROWS as usize] = {
+    let mut keys = [[0; COLUMNS as usize]; ROWS as usize];
+    let mut seed: u64 = 0xb10c_ced0_b10c_ced0;
+    let mut row = 0;
+    while row < ROWS as usize {
+        let mut column = 0;
+        while column < COLUMNS as usize {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            keys[row][column] = mix(seed);
+            column += 1;
+        }
+        row += 1;
+    }
+    keys
+};
+
+/// Key toggled when `Player::X` is to move
+pub(crate) const SIDE_TO_MOVE: u64 = mix(0x5eed);
+
+/// SplitMix64 finalizer
+const fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub(crate) fn piece(column: u8, row: u8, player: Player) -> u64 {
+    PIECES[player as usize][row as usize][column as usize]
+}
+
+pub(crate) fn blocked(column: u8, row: u8) -> u64 {
+    BLOCKED[row as usize][column as usize]
+}
+
+pub(crate) fn side_to_move(player: Player) -> u64 {
+    match player {
+        Player::O => 0,
+        Player::X => SIDE_TO_MOVE,
+    }
+}
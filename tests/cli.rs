@@ -0,0 +1,341 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    process::{Child, Command, Output, Stdio},
+};
+
+/// Runs the binary with the given arguments, feeding it `input`
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_connect4"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_human_vs_engine() {
+    // Invalid input first, then every column until it's full
+    let mut input = String::from("abc\n9\n\n");
+    for column in 0..7 {
+        for _ in 0..6 {
+            input.push_str(&format!("{}\n", column));
+        }
+    }
+    let output = run(
+        &["pvc", "--depth", "2", "--human", "o", "--seed", "1"],
+        &input,
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Please enter a column number"));
+    assert!(stdout.contains("Can't play there: column must be below 7"));
+    assert!(stdout.contains("Engine plays column"));
+    assert!(
+        stdout.contains("You win!")
+            || stdout.contains("The engine wins!")
+            || stdout.contains("Draw!")
+    );
+
+    let output = run(&["pvc", "--human", "y"], "");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_depth() {
+    let output = run(&["cvc", "--depth", "1", "--seed", "3"], "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Seed: 3\nDepth: 1\n"));
+    assert!(stdout.contains("Victory!") || stdout.contains("Draw!"));
+
+    let output = run(&["cvc", "--depth", "0"], "");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_board_size() {
+    let args = ["cvc", "--depth", "4", "--seed", "2"];
+    let output = run(
+        &[&args[..], &["--cols", "4", "--rows", "4", "--connect", "3"]].concat(),
+        "",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Board: 4 columns, 4 rows, connect 3\n"));
+    assert!(stdout.contains("Victory!") || stdout.contains("Draw!"));
+    // Only the first 4 columns are played
+    assert!(stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Player "))
+        .all(|line| ["0", "1", "2", "3"]
+            .iter()
+            .any(|column| line.ends_with(column))));
+
+    let output = run(&["cvc", "--depth", "1"], "");
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Board: 7 columns, 6 rows, connect 4\n"));
+    for invalid in [
+        &["--cols", "8"][..],
+        &["--rows", "0"],
+        &["--rows", "2", "--cols", "3", "--connect", "4"],
+        &["--connect", "0"],
+    ] {
+        for mode in [&["cvc"][..], &["--analyze", "3"], &["--analyze-game", "3"]] {
+            let output = run(&[mode, invalid].concat(), "");
+            assert!(!output.status.success());
+            assert!(output.stdout.is_empty());
+        }
+    }
+
+    // The other modes play on the smaller board as well
+    let small = ["--cols", "3", "--depth", "2"];
+    let output = run(&[&small[..], &["--analyze", "0b"]].concat(), "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout
+            .lines()
+            .filter(|line| line.starts_with("Column"))
+            .count(),
+        3
+    );
+    let output = run(&[&small[..], &["--analyze-game", "012"]].concat(), "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+    for moves in ["03", "0d"] {
+        assert!(!run(&[&small[..], &["--analyze", moves]].concat(), "")
+            .status
+            .success());
+        assert!(!run(&[&small[..], &["--analyze-game", moves]].concat(), "")
+            .status
+            .success());
+    }
+    let output = run(&["pvp", "--cols", "3"], "3\n?\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("your move (0-2 or a-c, q to quit)"));
+    assert!(stdout.contains("Can't play there: column must be below 3"));
+    assert!(stdout.contains("Please enter a column number: columns are 0 to 2 or a to c"));
+}
+
+#[test]
+fn test_engine_temperature() {
+    /// Columns played in a game between engines
+    fn moves(args: &[&str]) -> Vec<String> {
+        let output = run(&[&["cvc", "--depth", "2"], args].concat(), "");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains("plays column"))
+            .map(String::from)
+            .collect()
+    }
+
+    let first = moves(&["--seed", "1"]);
+    assert_eq!(moves(&["--seed", "2"]), first);
+    assert_eq!(moves(&["--seed", "2", "--temperature", "0"]), first);
+    assert!(
+        (1..10).any(|seed| moves(&["--seed", &seed.to_string(), "--temperature", "50"]) != first)
+    );
+}
+
+#[test]
+fn test_analyze() {
+    // O wins in column 0, any other move but blocking X in column 1 loses
+    let output = run(&["--analyze", "010101"], "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let columns: Vec<_> = stdout
+        .lines()
+        .filter(|line| line.starts_with("Column"))
+        .collect();
+    assert_eq!(columns.len(), 7);
+    let wins: Vec<_> = columns.iter().filter(|line| line.contains("win")).collect();
+    assert_eq!(wins.len(), 1);
+    assert!(wins[0].starts_with("Column 0:"));
+    assert!(wins[0].ends_with("forced win <- best"));
+    assert_eq!(
+        columns
+            .iter()
+            .filter(|line| line.contains("losing"))
+            .count(),
+        5
+    );
+    // Those lose because X completes the column right away
+    assert_eq!(
+        columns
+            .iter()
+            .filter(|line| line.ends_with("pruned, allows an immediate reply"))
+            .count(),
+        5
+    );
+    assert!(stdout.contains("Move      Eval"));
+
+    let output = run(&["--analyze", "01?"], "");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_analyze_game() {
+    // O throws away the win in column 0, then X wins in column 1
+    let output = run(&["--analyze-game", "01010121", "--depth", "4"], "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(
+        lines[6],
+        "7. O plays 2: losing in 2 plies, best 0: immediate win ?? blunder"
+    );
+    assert!(lines[7].starts_with("8. X plays 1: immediate win"));
+    assert!(!lines[7].contains("blunder"));
+
+    let output = run(&["--analyze-game", "0000000", "--depth", "2"], "");
+    assert!(!output.status.success());
+    let output = run(&["--analyze-game", "3x"], "");
+    assert!(!output.status.success());
+    // Columns as numbers or letters
+    let output = run(&["--analyze-game", "dD3", "--depth", "2"], "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout
+        .lines()
+        .zip(["O", "X", "O"])
+        .all(|(line, player)| line.contains(&format!("{} plays 3:", player))));
+}
+
+#[test]
+fn test_human_vs_human() {
+    // Columns as numbers or letters
+    let output = run(&["pvp"], "0\n1\na\n7\n?\nh\nB\n0\n1\n0\n");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Can't play there: column must be below 7"));
+    assert!(stdout.contains("Please enter a column number: columns are 0 to 6 or a to g"));
+    assert!(stdout.contains("Player O wins!"));
+    // The final board marks the winning line in the layout of every other board
+    assert!(stdout
+        .contains("0 1 2 3 4 5 6\n· · · · · · ·\n· · · · · · ·\n* · · · · · ·\n* X · · · · ·\n"));
+    assert!(!stdout.contains("Quitting"));
+
+    let output = run(&["pvp"], "3\nq\n3\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Quitting"));
+    assert_eq!(stdout.matches("your move").count(), 2);
+}
+
+#[test]
+fn test_protocol() {
+    let output = run(
+        &["--protocol"],
+        "position moves 3 3 4\ngo depth 4\nhello\nposition moves 0 b a 1 0 1 0\ngo\nnewgame\ngo depth 2\nquit\ngo\n",
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with("info eval "));
+    assert!(lines[1].starts_with("bestmove "));
+    assert_eq!(lines[2], "info string unknown command: hello");
+    // The position is already won
+    assert_eq!(lines[3], "bestmove none");
+    assert!(lines[5].starts_with("bestmove "));
+}
+
+/// Starts the binary in `--serve` mode on a free port and returns it with
+/// the address it listens on
+fn serve(args: &[&str]) -> (Child, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_connect4"))
+        .args(args)
+        .args(["--serve", "127.0.0.1:0", "--seed", "1"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Stays open until the child ends, the server would fail writing to it otherwise
+    let stdout = BufReader::new(child.stdout.as_mut().unwrap());
+    let address = stdout
+        .lines()
+        .map(Result::unwrap)
+        .find_map(|line| line.strip_prefix("Listening on ").map(String::from))
+        .unwrap();
+    (child, address)
+}
+
+/// Reads lines up to and including `last`
+fn read_until(reader: &mut impl BufRead, last: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        assert_ne!(reader.read_line(&mut line).unwrap(), 0, "{:?}", lines);
+        let line = line.trim_end().to_owned();
+        lines.push(line.clone());
+        if line == last {
+            return lines;
+        }
+    }
+}
+
+#[test]
+fn test_serve_pvp() {
+    let (mut server, address) = serve(&["pvp"]);
+    let mut o = TcpStream::connect(&address).unwrap();
+    let mut o_reader = BufReader::new(o.try_clone().unwrap());
+    let mut x = TcpStream::connect(&address).unwrap();
+    let mut x_reader = BufReader::new(x.try_clone().unwrap());
+
+    assert_eq!(
+        read_until(&mut o_reader, "your move"),
+        ["you play O", "your move"]
+    );
+    writeln!(o, "0").unwrap();
+    assert_eq!(read_until(&mut x_reader, "your move")[0], "you play X");
+    writeln!(x, "1").unwrap();
+    for _ in 0..2 {
+        read_until(&mut o_reader, "your move");
+        writeln!(o, "0").unwrap();
+        read_until(&mut x_reader, "your move");
+        writeln!(x, "1").unwrap();
+    }
+    read_until(&mut o_reader, "your move");
+    writeln!(o, "0").unwrap();
+
+    let lines = read_until(&mut x_reader, "winner O");
+    assert_eq!(lines[0], "played 1");
+    assert_eq!(
+        lines[lines.len() - 5..lines.len() - 1],
+        ["o......", "ox.....", "ox.....", "ox....."]
+    );
+    assert!(server.wait().unwrap().success());
+}
+
+#[test]
+fn test_serve_illegal_move() {
+    let (mut server, address) = serve(&["pvc", "--depth", "2", "--human", "x"]);
+    let mut x = TcpStream::connect(&address).unwrap();
+    let mut x_reader = BufReader::new(x.try_clone().unwrap());
+
+    let lines = read_until(&mut x_reader, "your move");
+    assert_eq!(lines[0], "you play X");
+    assert!(lines[1].starts_with("played "));
+    writeln!(x, "7").unwrap();
+    read_until(
+        &mut x_reader,
+        "error player X can't play there: column must be below 7",
+    );
+    assert!(server.wait().unwrap().success());
+}